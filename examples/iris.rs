@@ -21,6 +21,17 @@ fn main() {
     // Each of the classifieres has its own training state
     let mut training = teacher.new_training(&model);
 
+    // Map the names of the Iris classes to class indices
+    let labels = vikos::transform::LabelEncoder::fit(
+        csv::Reader::from_path(PATH)
+            .expect("File is ok")
+            .deserialize()
+            .map(|row| {
+                let (truth, _): (String, Features) = row.unwrap();
+                truth
+            }),
+    );
+
     // Read iris Data
     for epoch in 0..300 {
         let mut rdr = csv::Reader::from_path(PATH).expect("File is ok");
@@ -31,12 +42,9 @@ fn main() {
             // Learn event
             let (truth, features): (String, Features) = row.unwrap();
 
-            let class = match truth.as_ref() {
-                "setosa" => 0,
-                "versicolor" => 1,
-                "virginica" => 2,
-                _ => panic!("unknown Iris class: {}", truth),
-            };
+            let class = labels
+                .encode(&truth)
+                .unwrap_or_else(|| panic!("unknown Iris class: {}", truth));
 
             teacher.teach_event(&mut training, &mut model, &cost, &features, class);
 
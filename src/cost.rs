@@ -1,6 +1,6 @@
 //! Implementations of `Cost` trait
 
-use crate::{linear_algebra::Vector, Cost};
+use crate::{linear_algebra::Vector, Cost, SecondDerivative};
 
 /// Pass an instance of this type to a training algorithm to optimize for C=Error^2
 ///
@@ -18,6 +18,12 @@ impl Cost<f64> for LeastSquares {
     }
 }
 
+impl SecondDerivative<f64> for LeastSquares {
+    fn second_derivative(&self, _prediction: f64, _truth: f64) -> f64 {
+        2.0
+    }
+}
+
 /// Pass an instance of this type to a training algorithm to optimize for C=|Error|
 ///
 /// Optimizing a `model::Constant` for `LeastAbsoluteDeviation` should yield the median. Gradient
@@ -82,6 +88,12 @@ impl Cost<f64> for MaxLikelihood {
     }
 }
 
+impl SecondDerivative<f64> for MaxLikelihood {
+    fn second_derivative(&self, prediction: f64, truth: f64) -> f64 {
+        (1.0 - truth) / (1.0 - prediction).powi(2) + truth / prediction.powi(2)
+    }
+}
+
 impl Cost<bool> for MaxLikelihood {
     fn outer_derivative(&self, prediction: &f64, truth: bool) -> f64 {
         1. / if truth { -prediction } else { 1.0 - prediction }
@@ -91,6 +103,12 @@ impl Cost<bool> for MaxLikelihood {
     }
 }
 
+impl SecondDerivative<bool> for MaxLikelihood {
+    fn second_derivative(&self, prediction: f64, truth: bool) -> f64 {
+        1. / (if truth { prediction } else { 1.0 - prediction }).powi(2)
+    }
+}
+
 impl<V> Cost<usize, V> for MaxLikelihood
 where
     V: Vector,
@@ -107,11 +125,225 @@ where
     }
 }
 
+/// Focal loss `C=-a(1-p)^g ln(p)`, with `p` being the predicted probability of the true class
+///
+/// Compared to `MaxLikelihood` the focal loss reduces the cost of events which are already
+/// classified well, so training focuses on the hard examples. `gamma` controls how strongly well
+/// classified events are discounted, `alpha` is the weight of the positive class (the negative
+/// class is weighted with `1 - alpha`). With `gamma = 0` and `alpha = 0.5` the focal loss is half
+/// the negative log likelihood. For the derivative, predictions are kept at least `f64::EPSILON`
+/// away from `0` and `1`, so saturated models still receive a finite gradient.
+/// See [this paper](https://arxiv.org/abs/1708.02002) for more information.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+///
+/// let history = [([2.7, 2.5], false), ([1.4, 2.3], false), ([7.6, 2.7], true)];
+/// let cost = cost::Focal { gamma: 2.0, alpha: 0.25 };
+/// let mut model = model::Logistic::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(30).cloned());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Focal {
+    /// Focusing parameter. The larger, the less do well classified events contribute to the cost
+    pub gamma: f64,
+    /// Weight of the positive class
+    pub alpha: f64,
+}
+
+impl Cost<bool> for Focal {
+    fn outer_derivative(&self, prediction: &f64, truth: bool) -> f64 {
+        let (p, alpha, sign) = if truth {
+            (*prediction, self.alpha, 1.0)
+        } else {
+            (1.0 - prediction, 1.0 - self.alpha, -1.0)
+        };
+        // Saturated predictions of exactly 0 or 1 would yield `0 * inf` for `gamma < 1`
+        let p = p.clamp(f64::EPSILON, 1.0 - f64::EPSILON);
+        // derivative with respect to p, the predicted probability of the true class
+        let derivation =
+            self.gamma * (1.0 - p).powf(self.gamma - 1.0) * p.ln() - (1.0 - p).powf(self.gamma) / p;
+        sign * alpha * derivation
+    }
+    fn cost(&self, prediction: f64, truth: bool) -> f64 {
+        let (p, alpha) = if truth {
+            (prediction, self.alpha)
+        } else {
+            (1.0 - prediction, 1.0 - self.alpha)
+        };
+        -alpha * (1.0 - p).powf(self.gamma) * p.ln()
+    }
+}
+
+impl<V> Cost<usize, V> for Focal
+where
+    V: Vector,
+{
+    fn outer_derivative(&self, prediction: &V, truth: usize) -> V {
+        let mut derivation = prediction.clone();
+        for i in 0..prediction.dimension() {
+            *derivation.at_mut(i) = self.outer_derivative(&prediction.at(i), truth == i);
+        }
+        derivation
+    }
+    fn cost(&self, prediction: V, truth: usize) -> f64 {
+        (0..prediction.dimension()).fold(0.0, |s, i| s + self.cost(prediction.at(i), i == truth))
+    }
+}
+
+/// Scales the value and the derivative of the wrapped `cost` function by `weight`
+///
+/// Used to express that some events should have more influence during training than others. See
+/// `Teacher::teach_weighted_event`.
+#[derive(Clone, Copy, Debug)]
+pub struct Weighted<C> {
+    /// Cost function whose value is scaled
+    pub cost: C,
+    /// Factor applied to value and derivative of `cost`
+    pub weight: f64,
+}
+
+impl<C, Truth, Target> Cost<Truth, Target> for Weighted<C>
+where
+    C: Cost<Truth, Target>,
+    Target: Vector,
+{
+    fn outer_derivative(&self, prediction: &Target, truth: Truth) -> Target {
+        let mut derivation = self.cost.outer_derivative(prediction, truth);
+        for i in 0..derivation.dimension() {
+            *derivation.at_mut(i) *= self.weight;
+        }
+        derivation
+    }
+
+    fn cost(&self, prediction: Target, truth: Truth) -> f64 {
+        self.weight * self.cost.cost(prediction, truth)
+    }
+}
+
+impl<C, Truth> SecondDerivative<Truth> for Weighted<C>
+where
+    C: SecondDerivative<Truth>,
+{
+    fn second_derivative(&self, prediction: f64, truth: Truth) -> f64 {
+        self.weight * self.cost.second_derivative(prediction, truth)
+    }
+}
+
+/// Scales value and derivative of the wrapped `cost` function depending on the class of the truth
+///
+/// Useful if some classes are much rarer than others. Otherwise a classifier may learn to never
+/// predict the rare class at all. `weights[i]` is applied to events whose truth is the `i`-th
+/// class. For `bool` truths `false` is class `0` and `true` is class `1`. Classes without an
+/// entry in `weights` keep the unscaled cost, i.e. a weight of `1`.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+///
+/// let history = [([0.0], false), ([0.2], false), ([0.4], false), ([1.0], true)];
+/// let cost = cost::ClassWeighted::balanced(cost::MaxLikelihood {}, 2, history.iter().cloned());
+/// let mut model = model::Logistic::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(40).cloned());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClassWeighted<C> {
+    /// Cost function whose value is scaled
+    pub cost: C,
+    /// Factor applied to value and derivative of `cost` for each class
+    pub weights: Vec<f64>,
+}
+
+impl<C> ClassWeighted<C> {
+    /// Derives weights inversely proportional to the frequency of each class in `history`
+    ///
+    /// The weight of class `i` is `n / (num_classes * n_i)`, with `n` being the number of events
+    /// and `n_i` the number of events of class `i`. Classes which do not occur in `history` are
+    /// assigned a weight of `1`.
+    ///
+    /// # Panics
+    ///
+    /// If the truth of an event in `history` is not smaller than `num_classes`.
+    pub fn balanced<H, F, Y>(cost: C, num_classes: usize, history: H) -> Self
+    where
+        H: IntoIterator<Item = (F, Y)>,
+        Y: Into<usize>,
+    {
+        let mut counts = vec![0usize; num_classes];
+        for (_, truth) in history {
+            let class = truth.into();
+            assert!(
+                class < num_classes,
+                "Class {} of truth exceeds number of classes {}",
+                class,
+                num_classes
+            );
+            counts[class] += 1;
+        }
+        let total: usize = counts.iter().sum();
+        let weights = counts
+            .iter()
+            .map(|&count| {
+                if count == 0 {
+                    1.0
+                } else {
+                    total as f64 / (num_classes * count) as f64
+                }
+            })
+            .collect();
+        ClassWeighted { cost, weights }
+    }
+
+    /// Weight applied to events of `class`
+    fn weight(&self, class: usize) -> f64 {
+        self.weights.get(class).cloned().unwrap_or(1.0)
+    }
+}
+
+impl<C> Cost<bool> for ClassWeighted<C>
+where
+    C: Cost<bool>,
+{
+    fn outer_derivative(&self, prediction: &f64, truth: bool) -> f64 {
+        self.weight(usize::from(truth)) * self.cost.outer_derivative(prediction, truth)
+    }
+    fn cost(&self, prediction: f64, truth: bool) -> f64 {
+        self.weight(usize::from(truth)) * self.cost.cost(prediction, truth)
+    }
+}
+
+impl<C, V> Cost<usize, V> for ClassWeighted<C>
+where
+    C: Cost<usize, V>,
+    V: Vector,
+{
+    fn outer_derivative(&self, prediction: &V, truth: usize) -> V {
+        let mut derivation = self.cost.outer_derivative(prediction, truth);
+        let weight = self.weight(truth);
+        for i in 0..derivation.dimension() {
+            *derivation.at_mut(i) *= weight;
+        }
+        derivation
+    }
+    fn cost(&self, prediction: V, truth: usize) -> f64 {
+        self.weight(truth) * self.cost.cost(prediction, truth)
+    }
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::super::Cost;
-    use super::{LeastAbsoluteDeviation, LeastSquares, MaxLikelihood};
+    use super::super::{Cost, SecondDerivative};
+    use super::{
+        ClassWeighted, Focal, LeastAbsoluteDeviation, LeastSquares, MaxLikelihood, Weighted,
+    };
 
     // Approximates the derivation of the cost function
     fn approx_derivate<T: Copy>(cost: &impl Cost<T>, prediction: f64, truth: T) -> f64 {
@@ -133,6 +365,35 @@ mod test {
         (derivate - approx).abs()
     }
 
+    // Returns absolute difference between second derivate and approximation
+    fn check_second_derivate<T: Copy>(
+        cost: &impl SecondDerivative<T>,
+        prediction: f64,
+        truth: T,
+    ) -> f64 {
+        let epsilon = 0.00001;
+        let approx = (cost.outer_derivative(&(prediction + epsilon), truth)
+            - cost.outer_derivative(&(prediction - epsilon), truth))
+            / (2.0 * epsilon);
+        let derivate = cost.second_derivative(prediction, truth);
+        println!("second derivation: {}, approximation: {}", derivate, approx);
+        (derivate - approx).abs()
+    }
+
+    #[test]
+    fn second_derivation() {
+        assert!(check_second_derivate(&LeastSquares {}, 10.0, 12.0) < 0.001);
+        let cost = MaxLikelihood {};
+        assert!(check_second_derivate(&cost, 0.2, false) < 0.001);
+        assert!(check_second_derivate(&cost, 0.8, true) < 0.001);
+        assert!(check_second_derivate(&cost, 0.3, 0.6) < 0.001);
+        let cost = Weighted {
+            cost: MaxLikelihood {},
+            weight: 3.0,
+        };
+        assert!(check_second_derivate(&cost, 0.8, false) < 0.001);
+    }
+
     #[test]
     fn least_squares_derivation() {
         let cost = LeastSquares {};
@@ -163,4 +424,122 @@ mod test {
             cost.outer_derivative(&0.8, 1.0)
         );
     }
+
+    #[test]
+    fn focal_derivation() {
+        let cost = Focal {
+            gamma: 2.0,
+            alpha: 0.25,
+        };
+        assert!(check_derivate(&cost, 0.2, false) < 0.001);
+        assert!(check_derivate(&cost, 0.8, true) < 0.001);
+        assert!(check_derivate(&cost, 0.8, false) < 0.001);
+        assert!(check_derivate(&cost, 0.2, true) < 0.001);
+        let cost = Focal {
+            gamma: 0.5,
+            alpha: 0.5,
+        };
+        assert!(check_derivate(&cost, 0.3, false) < 0.001);
+        assert!(check_derivate(&cost, 0.7, true) < 0.001);
+    }
+
+    #[test]
+    fn focal_derivation_saturated() {
+        for &gamma in &[0.0, 0.5, 2.0] {
+            let cost = Focal { gamma, alpha: 0.5 };
+            for &p in &[0.0, 1.0] {
+                let positive = cost.outer_derivative(&p, true);
+                let negative = cost.outer_derivative(&p, false);
+                println!("gamma: {}, p: {}: {} {}", gamma, p, positive, negative);
+                assert!(positive.is_finite() && positive <= 0.0);
+                assert!(negative.is_finite() && negative >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn focal_without_focus_is_half_max_likelihood() {
+        let focal = Focal {
+            gamma: 0.0,
+            alpha: 0.5,
+        };
+        let likelihood = MaxLikelihood {};
+        assert!((focal.cost(0.2, false) - 0.5 * likelihood.cost(0.2, false)).abs() < 1e-12);
+        assert!(
+            (focal.outer_derivative(&0.8, true) - 0.5 * likelihood.outer_derivative(&0.8, true))
+                .abs()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn focal_multi_class_derivation() {
+        let cost = Focal {
+            gamma: 2.0,
+            alpha: 0.25,
+        };
+        let derivation = cost.outer_derivative(&[0.2, 0.7], 1);
+        assert_eq!(cost.outer_derivative(&0.2, false), derivation[0]);
+        assert_eq!(cost.outer_derivative(&0.7, true), derivation[1]);
+        assert_eq!(
+            cost.cost(0.2, false) + cost.cost(0.7, true),
+            cost.cost([0.2, 0.7], 1)
+        );
+    }
+
+    #[test]
+    fn weighted_derivation() {
+        let cost = Weighted {
+            cost: MaxLikelihood {},
+            weight: 3.0,
+        };
+        assert!(check_derivate(&cost, 0.2, false) < 0.001);
+        assert!(check_derivate(&cost, 0.8, true) < 0.001);
+        assert_eq!(
+            3.0 * MaxLikelihood {}.outer_derivative(&0.2, false),
+            cost.outer_derivative(&0.2, false)
+        );
+    }
+
+    #[test]
+    fn class_weighted_derivation() {
+        let cost = ClassWeighted {
+            cost: MaxLikelihood {},
+            weights: vec![0.5, 4.0],
+        };
+        assert!(check_derivate(&cost, 0.2, false) < 0.001);
+        assert!(check_derivate(&cost, 0.8, true) < 0.001);
+        assert_eq!(
+            0.5 * MaxLikelihood {}.cost(0.2, false),
+            cost.cost(0.2, false)
+        );
+        assert_eq!(4.0 * MaxLikelihood {}.cost(0.2, true), cost.cost(0.2, true));
+    }
+
+    #[test]
+    fn balanced_class_weights() {
+        let history = [((), 0usize), ((), 0), ((), 0), ((), 1), ((), 0), ((), 1)];
+        let cost = ClassWeighted::balanced(MaxLikelihood {}, 3, history.iter().cloned());
+        assert_eq!(vec![0.5, 1.0, 1.0], cost.weights);
+    }
+
+    #[test]
+    #[should_panic(expected = "Class 2 of truth exceeds number of classes 2")]
+    fn balanced_class_weights_unknown_class() {
+        let history = [((), 0usize), ((), 2)];
+        ClassWeighted::balanced(MaxLikelihood {}, 2, history.iter().cloned());
+    }
+
+    #[test]
+    fn class_weighted_unknown_class() {
+        let cost = ClassWeighted {
+            cost: MaxLikelihood {},
+            weights: vec![2.0],
+        };
+        assert_eq!(MaxLikelihood {}.cost(0.2, true), cost.cost(0.2, true));
+        assert_eq!(
+            MaxLikelihood {}.cost([0.2, 0.7], 1),
+            cost.cost([0.2, 0.7], 1)
+        );
+    }
 }
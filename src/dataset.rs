@@ -0,0 +1,752 @@
+//! Reading and writing histories from and to files
+//!
+//! Supports the sparse LIBSVM (or SVMlight) text format. Reading CSV files requires the `csv`
+//! cargo feature. Histories can be shuffled with `shuffle` and replayed with `epochs`.
+
+use crate::linear_algebra::{SparseVector, Vector};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{
+    error, fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+/// Truth types which can be parsed from and written to a text field
+pub trait Label: Sized {
+    /// Parses `field`, returns `None` if it does not represent a valid label
+    fn parse_label(field: &str) -> Option<Self>;
+
+    /// Text representation of the label, which can be parsed by `parse_label`
+    fn format_label(&self) -> String;
+}
+
+impl Label for f64 {
+    fn parse_label(field: &str) -> Option<f64> {
+        field.trim().parse().ok()
+    }
+
+    fn format_label(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Accepts `true`, `1` and `+1` for `true`, as well as `false`, `0` and `-1` for `false`. Written
+/// as `+1` and `-1`.
+impl Label for bool {
+    fn parse_label(field: &str) -> Option<bool> {
+        match field.trim() {
+            "true" | "1" | "+1" => Some(true),
+            "false" | "0" | "-1" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn format_label(&self) -> String {
+        if *self { "+1" } else { "-1" }.to_string()
+    }
+}
+
+impl Label for usize {
+    fn parse_label(field: &str) -> Option<usize> {
+        field.trim().parse().ok()
+    }
+
+    fn format_label(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Errors occurring while reading a history
+///
+/// Variants may be added in the future, e.g. for new file formats or optional features.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Error reading from the underlying file
+    Io(io::Error),
+    /// Error reported by the CSV reader
+    #[cfg(feature = "csv")]
+    Csv(csv::Error),
+    /// A column passed by name is not part of the header
+    MissingColumn(String),
+    /// A field could not be parsed
+    Parse {
+        /// Line in the file, starting at one
+        line: u64,
+        /// Describes the field, e.g. the name of its column
+        field: String,
+        /// Content of the field
+        value: String,
+    },
+    /// A line violates the structure of the file format
+    Malformed {
+        /// Line in the file, starting at one
+        line: u64,
+        /// What is wrong with the line
+        reason: String,
+    },
+    /// The number of feature columns does not match the dimension of the feature vector
+    Dimension {
+        /// Dimension of the feature vector
+        expected: usize,
+        /// Number of feature columns
+        actual: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            #[cfg(feature = "csv")]
+            Error::Csv(error) => write!(f, "{}", error),
+            Error::MissingColumn(name) => write!(f, "column '{}' not found in header", name),
+            Error::Parse { line, field, value } => write!(
+                f,
+                "line {}: could not parse '{}' in field '{}'",
+                line, value, field
+            ),
+            Error::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+            Error::Dimension { expected, actual } => write!(
+                f,
+                "{} feature columns given, but features have dimension {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Error {
+        Error::Csv(error)
+    }
+}
+
+/// Reads events from a CSV file lazily
+///
+/// Each row is turned into features of type `V` (e.g. `Vec<f64>` or `[f64; 4]`) and a truth of
+/// type `Y` (e.g. `f64`, `bool` or `usize`). Columns are selected by their name in the header.
+/// The iterator yields an error for each row which can not be parsed, so it can be passed to
+/// `try_learn_history` directly.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, dataset::CsvHistory, model, teacher, try_learn_history};
+///
+/// let data = "x,y\n0.0,3.0\n1.0,4.0\n2.0,5.0\n";
+/// let history = CsvHistory::from_reader(data.as_bytes(), &["x"], "y").unwrap();
+/// let mut model = model::Linear::<[f64; 1]>::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.2 };
+/// let cost = cost::LeastSquares {};
+///
+/// try_learn_history(&teacher, &cost, &mut model, history).unwrap();
+/// ```
+#[cfg(feature = "csv")]
+pub struct CsvHistory<R, V, Y> {
+    records: csv::StringRecordsIntoIter<R>,
+    /// Name and index of each feature column
+    features: Vec<(String, usize)>,
+    /// Name and index of the target column
+    target: (String, usize),
+    event: PhantomData<(V, Y)>,
+}
+
+#[cfg(feature = "csv")]
+impl<V: Vector, Y> CsvHistory<File, V, Y> {
+    /// Opens the CSV file at `path`, using the columns named in `features` and `target`
+    ///
+    /// Fails with `Error::Dimension` if `V` has a fixed dimension different from the number of
+    /// `features`.
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        features: &[&str],
+        target: &str,
+    ) -> Result<Self, Error> {
+        Self::from_csv(csv::Reader::from_path(path)?, features, target)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<R: io::Read, V: Vector, Y> CsvHistory<R, V, Y> {
+    /// Reads CSV from `reader`, using the columns named in `features` and `target`
+    ///
+    /// Fails with `Error::Dimension` if `V` has a fixed dimension different from the number of
+    /// `features`.
+    pub fn from_reader(reader: R, features: &[&str], target: &str) -> Result<Self, Error> {
+        Self::from_csv(csv::Reader::from_reader(reader), features, target)
+    }
+
+    fn from_csv(
+        mut reader: csv::Reader<R>,
+        features: &[&str],
+        target: &str,
+    ) -> Result<Self, Error> {
+        match V::fixed_dimension() {
+            Some(expected) if expected != features.len() => {
+                return Err(Error::Dimension {
+                    expected,
+                    actual: features.len(),
+                })
+            }
+            _ => (),
+        }
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header == name)
+                .map(|index| (name.to_string(), index))
+                .ok_or_else(|| Error::MissingColumn(name.to_string()))
+        };
+        Ok(CsvHistory {
+            features: features
+                .iter()
+                .map(|name| column(name))
+                .collect::<Result<_, _>>()?,
+            target: column(target)?,
+            records: reader.into_records(),
+            event: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<R, V, Y> Iterator for CsvHistory<R, V, Y>
+where
+    R: io::Read,
+    V: Vector,
+    Y: Label,
+{
+    type Item = Result<(V, Y), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(error) => return Some(Err(error.into())),
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let parse_error = |name: &str, value: &str| Error::Parse {
+            line,
+            field: name.to_string(),
+            value: value.to_string(),
+        };
+
+        let mut features = V::zero_from_dimension(self.features.len());
+        for (i, (name, index)) in self.features.iter().enumerate() {
+            let value = record.get(*index).unwrap_or("");
+            match value.trim().parse() {
+                Ok(x) => *features.at_mut(i) = x,
+                Err(_) => return Some(Err(parse_error(name, value))),
+            }
+        }
+        let (name, index) = &self.target;
+        let value = record.get(*index).unwrap_or("");
+        Some(match Y::parse_label(value) {
+            Some(truth) => Ok((features, truth)),
+            None => Err(parse_error(name, value)),
+        })
+    }
+}
+
+/// Event read from a line in the LIBSVM format
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibSvmRecord<Y> {
+    /// Label in front of the line
+    pub truth: Y,
+    /// Query id, used to group events for ranking
+    pub qid: Option<u64>,
+    /// Features of the event
+    pub features: SparseVector,
+}
+
+/// Reads events in the sparse LIBSVM (or SVMlight) text format lazily
+///
+/// Each line has the form `<label> [qid:<qid>] <index>:<value> ...`. Indices start at one and
+/// must be ascending. Everything following a `#` is a comment. Empty lines are skipped. Index `i`
+/// is stored at position `i - 1` in the features, which have the dimension passed on
+/// construction.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, dataset::LibSvmReader, model, teacher, try_learn_history};
+/// use vikos::linear_algebra::SparseVector;
+///
+/// let data = "3.0 1:0.5\n-1.0 2:1.5 # comment\n4.0 1:1.0 2:0.5\n";
+/// let history = LibSvmReader::<_, f64>::from_reader(data.as_bytes(), 2);
+/// let mut model = model::Linear::<SparseVector>::with_feature_dimension(2);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.2 };
+/// let cost = cost::LeastSquares {};
+///
+/// try_learn_history(&teacher, &cost, &mut model, history.events()).unwrap();
+/// ```
+pub struct LibSvmReader<R, Y> {
+    lines: io::Lines<R>,
+    line: u64,
+    dimension: usize,
+    truth: PhantomData<Y>,
+}
+
+impl<Y> LibSvmReader<BufReader<File>, Y> {
+    /// Opens the file at `path`. Features have the specified `dimension`.
+    pub fn from_path<P: AsRef<Path>>(path: P, dimension: usize) -> Result<Self, Error> {
+        Ok(Self::from_reader(
+            BufReader::new(File::open(path)?),
+            dimension,
+        ))
+    }
+}
+
+impl<R: BufRead, Y> LibSvmReader<R, Y> {
+    /// Reads lines from `reader`. Features have the specified `dimension`.
+    pub fn from_reader(reader: R, dimension: usize) -> Self {
+        LibSvmReader {
+            lines: reader.lines(),
+            line: 0,
+            dimension,
+            truth: PhantomData,
+        }
+    }
+
+    /// Tuples of features and truth, as expected by `try_learn_history`. Drops query ids.
+    pub fn events(self) -> impl Iterator<Item = Result<(SparseVector, Y), Error>>
+    where
+        Y: Label,
+    {
+        self.map(|record| record.map(|record| (record.features, record.truth)))
+    }
+}
+
+impl<R: BufRead, Y: Label> LibSvmReader<R, Y> {
+    fn parse(&self, text: &str) -> Result<LibSvmRecord<Y>, Error> {
+        let line = self.line;
+        let parse_error = |field: &str, value: &str| Error::Parse {
+            line,
+            field: field.to_string(),
+            value: value.to_string(),
+        };
+        let malformed = |reason: String| Error::Malformed { line, reason };
+
+        let mut tokens = text.split_whitespace().peekable();
+        let label = tokens.next().expect("Line must not be empty");
+        let truth = Y::parse_label(label).ok_or_else(|| parse_error("label", label))?;
+        let qid = match tokens.peek() {
+            Some(token) if token.starts_with("qid:") => {
+                let value = &token["qid:".len()..];
+                tokens.next();
+                Some(value.parse().map_err(|_| parse_error("qid", value))?)
+            }
+            _ => None,
+        };
+
+        let mut features = SparseVector::zero_from_dimension(self.dimension);
+        let mut previous = 0;
+        for token in tokens {
+            let separator = token
+                .find(':')
+                .ok_or_else(|| malformed(format!("expected 'index:value', found '{}'", token)))?;
+            let (index, value) = (&token[..separator], &token[separator + 1..]);
+            let index: usize = index.parse().map_err(|_| parse_error("index", index))?;
+            if index == 0 || index > self.dimension {
+                return Err(malformed(format!(
+                    "index {} out of range 1 to {}",
+                    index, self.dimension
+                )));
+            }
+            if index <= previous {
+                return Err(malformed(format!(
+                    "indices must be ascending, but {} follows {}",
+                    index, previous
+                )));
+            }
+            previous = index;
+            *features.at_mut(index - 1) = value
+                .parse()
+                .map_err(|_| parse_error(&format!("value of index {}", index), value))?;
+        }
+
+        Ok(LibSvmRecord {
+            truth,
+            qid,
+            features,
+        })
+    }
+}
+
+impl<R: BufRead, Y: Label> Iterator for LibSvmReader<R, Y> {
+    type Item = Result<LibSvmRecord<Y>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(error) => return Some(Err(error.into())),
+            };
+            self.line += 1;
+            let content = text.split('#').next().unwrap_or("");
+            if !content.trim().is_empty() {
+                return Some(self.parse(content));
+            }
+        }
+    }
+}
+
+/// Writes events in the sparse LIBSVM (or SVMlight) text format
+///
+/// Only non-zero features are written. The feature at position `i` is written with index `i + 1`.
+///
+/// # Example
+///
+/// ```
+/// use vikos::dataset::LibSvmWriter;
+///
+/// let mut writer = LibSvmWriter::new(Vec::new());
+/// writer.write(&[0.0, 2.5, 1.0], &true, Some(7)).unwrap();
+/// assert_eq!(&b"+1 qid:7 2:2.5 3:1\n"[..], &writer.into_inner()[..]);
+/// ```
+pub struct LibSvmWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> LibSvmWriter<W> {
+    /// Writes lines to `writer`
+    pub fn new(writer: W) -> Self {
+        LibSvmWriter { writer }
+    }
+
+    /// Writes a single event as one line, with an optional query id
+    pub fn write<V, Y>(&mut self, features: &V, truth: &Y, qid: Option<u64>) -> io::Result<()>
+    where
+        V: Vector,
+        Y: Label,
+    {
+        write!(self.writer, "{}", truth.format_label())?;
+        if let Some(qid) = qid {
+            write!(self.writer, " qid:{}", qid)?;
+        }
+        for i in 0..features.dimension() {
+            let value = features.at(i);
+            if value != 0.0 {
+                write!(self.writer, " {}:{}", i + 1, value)?;
+            }
+        }
+        writeln!(self.writer)
+    }
+
+    /// Writes all events in `history`, without query ids
+    pub fn write_history<V, Y, H>(&mut self, history: H) -> io::Result<()>
+    where
+        V: Vector,
+        Y: Label,
+        H: IntoIterator<Item = (V, Y)>,
+    {
+        for (features, truth) in history {
+            self.write(&features, &truth, None)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Shuffles a stream of events using a bounded buffer
+///
+/// Created by `shuffle`. Events are read into the buffer until it is full. Then each call to
+/// `next` emits a randomly chosen event from the buffer and replaces it with the next event of the
+/// stream. The larger the buffer, the closer the result is to a uniform shuffle of the entire
+/// stream.
+pub struct Shuffled<I: Iterator> {
+    events: I,
+    buffer: Vec<I::Item>,
+    capacity: usize,
+    rng: StdRng,
+}
+
+/// Shuffles `history` lazily, holding at most `capacity` events in memory
+///
+/// Use this to break up the order of histories which are sorted, e.g. by time, before passing
+/// them to `learn_history`. The same `seed` always yields the same order. Panics if `capacity` is
+/// zero.
+///
+/// # Example
+///
+/// ```
+/// use vikos::dataset::shuffle;
+///
+/// let shuffled: Vec<_> = shuffle(0..10, 4, 42).collect();
+/// assert_eq!(10, shuffled.len());
+/// assert_ne!((0..10).collect::<Vec<_>>(), shuffled);
+/// ```
+pub fn shuffle<H>(history: H, capacity: usize, seed: u64) -> Shuffled<H::IntoIter>
+where
+    H: IntoIterator,
+{
+    assert!(capacity > 0, "Capacity of shuffle buffer must not be zero");
+    Shuffled {
+        events: history.into_iter(),
+        buffer: Vec::with_capacity(capacity),
+        capacity,
+        rng: StdRng::seed_from_u64(seed),
+    }
+}
+
+impl<I: Iterator> Iterator for Shuffled<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        while self.buffer.len() < self.capacity {
+            match self.events.next() {
+                Some(event) => self.buffer.push(event),
+                None => break,
+            }
+        }
+        if self.buffer.is_empty() {
+            None
+        } else {
+            let index = self.rng.gen_range(0..self.buffer.len());
+            Some(self.buffer.swap_remove(index))
+        }
+    }
+}
+
+/// Replays an in memory history for several epochs
+///
+/// Created by `epochs`.
+pub struct Epochs<'a, T> {
+    history: &'a [T],
+    order: Vec<usize>,
+    position: usize,
+    remaining: usize,
+    rng: StdRng,
+}
+
+/// Iterates over `history` `epochs` times, in a different random order each epoch
+///
+/// Each event occurs exactly once per epoch. The same `seed` always yields the same order.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, dataset::epochs, learn_history, model, teacher};
+///
+/// let history = [([0.0], 3.0), ([1.0], 4.0), ([2.0], 5.0)];
+/// let mut model = model::Linear::<[f64; 1]>::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, epochs(&history, 500, 7));
+/// assert!((model.m[0] - 1.0).abs() < 0.01);
+/// ```
+pub fn epochs<T: Clone>(history: &[T], epochs: usize, seed: u64) -> Epochs<'_, T> {
+    Epochs {
+        history,
+        order: (0..history.len()).collect(),
+        position: history.len(),
+        remaining: epochs,
+        rng: StdRng::seed_from_u64(seed),
+    }
+}
+
+impl<'a, T: Clone> Iterator for Epochs<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.position == self.order.len() {
+            if self.remaining == 0 || self.order.is_empty() {
+                return None;
+            }
+            self.remaining -= 1;
+            self.order.shuffle(&mut self.rng);
+            self.position = 0;
+        }
+        let event = self.history[self.order[self.position]].clone();
+        self.position += 1;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{epochs, shuffle, Error, Label, LibSvmReader, LibSvmRecord, LibSvmWriter};
+    use crate::linear_algebra::SparseVector;
+
+    #[test]
+    fn parse_labels() {
+        assert_eq!(Some(true), bool::parse_label("+1"));
+        assert_eq!(Some(false), bool::parse_label("-1"));
+        assert_eq!(Some(true), bool::parse_label("true"));
+        assert_eq!(None, bool::parse_label("2"));
+        assert_eq!(Some(2), usize::parse_label(" 2"));
+        assert_eq!(None, usize::parse_label("-2"));
+        assert_eq!(Some(-2.5), f64::parse_label("-2.5"));
+    }
+
+    #[test]
+    fn shuffle_is_permutation() {
+        let mut shuffled: Vec<_> = shuffle(0..100, 10, 3).collect();
+        assert_eq!(shuffled, shuffle(0..100, 10, 3).collect::<Vec<_>>());
+        assert_ne!(shuffled, shuffle(0..100, 10, 4).collect::<Vec<_>>());
+        shuffled.sort();
+        assert_eq!((0..100).collect::<Vec<_>>(), shuffled);
+
+        // A buffer of one can not change the order
+        assert_eq!(
+            (0..10).collect::<Vec<_>>(),
+            shuffle(0..10, 1, 3).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn epochs_visit_each_event_once() {
+        let history: Vec<_> = (0..20).collect();
+        let replay: Vec<_> = epochs(&history, 3, 5).collect();
+        assert_eq!(60, replay.len());
+        for epoch in replay.chunks(20) {
+            let mut epoch = epoch.to_vec();
+            epoch.sort();
+            assert_eq!(history, epoch);
+        }
+        assert_ne!(replay[..20], replay[20..40]);
+        assert_eq!(0, epochs(&history, 0, 5).count());
+        assert_eq!(0, epochs::<u8>(&[], 3, 5).count());
+    }
+
+    #[test]
+    fn read_libsvm() {
+        let data = "# header\n2 qid:1 1:0.5 3:-1 # first\n\n0 qid:2 2:4e1\n";
+        let records: Vec<_> = LibSvmReader::<_, usize>::from_reader(data.as_bytes(), 3)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            vec![
+                LibSvmRecord {
+                    truth: 2,
+                    qid: Some(1),
+                    features: SparseVector::from_entries(3, vec![(0, 0.5), (2, -1.0)]),
+                },
+                LibSvmRecord {
+                    truth: 0,
+                    qid: Some(2),
+                    features: SparseVector::from_entries(3, vec![(1, 40.0)]),
+                },
+            ],
+            records
+        );
+    }
+
+    #[test]
+    fn malformed_libsvm() {
+        let data = "+1 1:1\nx 1:1\n-1 2:1 1:1\n+1 4:1\n+1 1\n-1 qid:a\n+1 1:b\n";
+        let errors: Vec<_> = LibSvmReader::<_, bool>::from_reader(data.as_bytes(), 3)
+            .map(|record| record.err().map(|error| error.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                Some("line 2: could not parse 'x' in field 'label'".to_string()),
+                Some("line 3: indices must be ascending, but 1 follows 2".to_string()),
+                Some("line 4: index 4 out of range 1 to 3".to_string()),
+                Some("line 5: expected 'index:value', found '1'".to_string()),
+                Some("line 6: could not parse 'a' in field 'qid'".to_string()),
+                Some("line 7: could not parse 'b' in field 'value of index 1'".to_string()),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn libsvm_round_trip() {
+        let history = vec![
+            (SparseVector::from_entries(4, vec![(3, 1.5)]), -2.0),
+            (
+                SparseVector::from_entries(4, vec![(0, 1.0), (1, 0.25)]),
+                0.5,
+            ),
+        ];
+        let mut writer = LibSvmWriter::new(Vec::new());
+        writer.write_history(history.clone()).unwrap();
+        let data = writer.into_inner();
+        assert_eq!("-2 4:1.5\n0.5 1:1 2:0.25\n", String::from_utf8_lossy(&data));
+
+        let read: Result<Vec<_>, Error> =
+            LibSvmReader::from_reader(&data[..], 4).events().collect();
+        assert_eq!(history, read.unwrap());
+    }
+
+    #[cfg(feature = "csv")]
+    mod csv {
+
+        use super::super::{CsvHistory, Error};
+
+        const DATA: &str = "name,a,b,label\nfirst,1.0,2.0,1\nsecond,3.0,x,0\nthird,5.0,6.0,7\n";
+
+        #[test]
+        fn read_rows() {
+            let mut history = CsvHistory::<_, Vec<f64>, usize>::from_reader(
+                DATA.as_bytes(),
+                &["b", "a"],
+                "label",
+            )
+            .unwrap();
+            assert_eq!(vec![2.0, 1.0], history.next().unwrap().unwrap().0);
+            match history.next().unwrap() {
+                Err(Error::Parse { line, field, value }) => {
+                    assert_eq!(3, line);
+                    assert_eq!("b", field);
+                    assert_eq!("x", value);
+                }
+                other => panic!("expected parse error, got {:?}", other),
+            }
+            assert_eq!((vec![6.0, 5.0], 7), history.next().unwrap().unwrap());
+            assert!(history.next().is_none());
+        }
+
+        #[test]
+        fn invalid_label() {
+            let history =
+                CsvHistory::<_, [f64; 1], bool>::from_reader(DATA.as_bytes(), &["a"], "label")
+                    .unwrap();
+            let errors: Vec<_> = history.filter_map(Result::err).collect();
+            assert_eq!(1, errors.len());
+            assert_eq!(
+                "line 4: could not parse '7' in field 'label'",
+                errors[0].to_string()
+            );
+        }
+
+        #[test]
+        fn dimension_mismatch() {
+            match CsvHistory::<_, [f64; 2], f64>::from_reader(DATA.as_bytes(), &["a"], "label") {
+                Err(Error::Dimension { expected, actual }) => {
+                    assert_eq!(2, expected);
+                    assert_eq!(1, actual);
+                }
+                _ => panic!("expected dimension mismatch"),
+            }
+        }
+
+        #[test]
+        fn missing_column() {
+            match CsvHistory::<_, Vec<f64>, f64>::from_reader(DATA.as_bytes(), &["c"], "label") {
+                Err(Error::MissingColumn(name)) => assert_eq!("c", name),
+                _ => panic!("expected missing column"),
+            }
+        }
+    }
+}
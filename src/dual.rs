@@ -0,0 +1,302 @@
+//! Forward mode automatic differentiation using dual numbers
+//!
+//! A dual number `a + b*e` extends a real number `a` with an infinitesimal part `b`, with
+//! `e^2 = 0`. Evaluating a function `f` at `x + e` yields `f(x) + f'(x)*e`, so the derivative is
+//! computed alongside the value. Write a function generically over `Number` and it can be
+//! evaluated for plain `f64` and for `Dual` alike.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Numbers over which functions can be defined generically, so they may be derived automatically
+///
+/// Implemented for `f64` and `Dual`.
+pub trait Number:
+    Copy
+    + From<f64>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+{
+    /// Real part of the number
+    fn value(self) -> f64;
+    /// `e^self`
+    fn exp(self) -> Self;
+    /// Natural logarithm
+    fn ln(self) -> Self;
+    /// Raises `self` to an integer power
+    fn powi(self, n: i32) -> Self;
+    /// Raises `self` to a floating point power
+    fn powf(self, n: f64) -> Self;
+    /// Square root
+    fn sqrt(self) -> Self;
+    /// Sine
+    fn sin(self) -> Self;
+    /// Cosine
+    fn cos(self) -> Self;
+    /// Hyperbolic tangent
+    fn tanh(self) -> Self;
+    /// Absolute value
+    fn abs(self) -> Self;
+}
+
+impl Number for f64 {
+    fn value(self) -> f64 {
+        self
+    }
+    fn exp(self) -> f64 {
+        f64::exp(self)
+    }
+    fn ln(self) -> f64 {
+        f64::ln(self)
+    }
+    fn powi(self, n: i32) -> f64 {
+        f64::powi(self, n)
+    }
+    fn powf(self, n: f64) -> f64 {
+        f64::powf(self, n)
+    }
+    fn sqrt(self) -> f64 {
+        f64::sqrt(self)
+    }
+    fn sin(self) -> f64 {
+        f64::sin(self)
+    }
+    fn cos(self) -> f64 {
+        f64::cos(self)
+    }
+    fn tanh(self) -> f64 {
+        f64::tanh(self)
+    }
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+}
+
+/// Number carrying its derivative with respect to a single variable
+///
+/// # Example
+///
+/// ```
+/// use vikos::dual::{Dual, Number};
+///
+/// // f(x) = x^2 + 3x
+/// fn f<N: Number>(x: N) -> N {
+///     x * x + x * 3.0
+/// }
+///
+/// let y = f(Dual::variable(2.0));
+/// assert_eq!(10.0, y.value);
+/// assert_eq!(7.0, y.derivative);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dual {
+    /// Value of the function
+    pub value: f64,
+    /// Derivative of the function with respect to the variable
+    pub derivative: f64,
+}
+
+impl Dual {
+    /// A number which does not depend on the variable. Its derivative is zero.
+    pub fn constant(value: f64) -> Dual {
+        Dual {
+            value,
+            derivative: 0.0,
+        }
+    }
+
+    /// The variable itself. Its derivative is one.
+    pub fn variable(value: f64) -> Dual {
+        Dual {
+            value,
+            derivative: 1.0,
+        }
+    }
+
+    /// Applies the chain rule for an outer function with value `f` and derivative `df`
+    fn chain(self, f: f64, df: f64) -> Dual {
+        Dual {
+            value: f,
+            derivative: df * self.derivative,
+        }
+    }
+}
+
+impl From<f64> for Dual {
+    fn from(value: f64) -> Dual {
+        Dual::constant(value)
+    }
+}
+
+impl Number for Dual {
+    fn value(self) -> f64 {
+        self.value
+    }
+    fn exp(self) -> Dual {
+        let e = self.value.exp();
+        self.chain(e, e)
+    }
+    fn ln(self) -> Dual {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+    fn powi(self, n: i32) -> Dual {
+        self.chain(self.value.powi(n), f64::from(n) * self.value.powi(n - 1))
+    }
+    fn powf(self, n: f64) -> Dual {
+        self.chain(self.value.powf(n), n * self.value.powf(n - 1.0))
+    }
+    fn sqrt(self) -> Dual {
+        let s = self.value.sqrt();
+        self.chain(s, 0.5 / s)
+    }
+    fn sin(self) -> Dual {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+    fn cos(self) -> Dual {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+    fn tanh(self) -> Dual {
+        let t = self.value.tanh();
+        self.chain(t, 1.0 - t * t)
+    }
+    fn abs(self) -> Dual {
+        self.chain(self.value.abs(), self.value.signum())
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value + other.value,
+            derivative: self.derivative + other.derivative,
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value - other.value,
+            derivative: self.derivative - other.derivative,
+        }
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    fn mul(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value * other.value,
+            derivative: self.derivative * other.value + self.value * other.derivative,
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    fn div(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value / other.value,
+            derivative: (self.derivative * other.value - self.value * other.derivative)
+                / (other.value * other.value),
+        }
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        Dual {
+            value: -self.value,
+            derivative: -self.derivative,
+        }
+    }
+}
+
+macro_rules! mixed_op_impl {
+    ($op:ident, $method:ident) => {
+        impl $op<f64> for Dual {
+            type Output = Dual;
+            fn $method(self, other: f64) -> Dual {
+                self.$method(Dual::constant(other))
+            }
+        }
+
+        impl $op<Dual> for f64 {
+            type Output = Dual;
+            fn $method(self, other: Dual) -> Dual {
+                Dual::constant(self).$method(other)
+            }
+        }
+    };
+}
+
+mixed_op_impl! { Add, add }
+mixed_op_impl! { Sub, sub }
+mixed_op_impl! { Mul, mul }
+mixed_op_impl! { Div, div }
+
+/// Prediction of a `Model` defined generically over `Number`
+///
+/// `model::AutoDiff` uses implementations of this trait to derive `Model::gradient`
+/// automatically.
+pub trait Function {
+    /// Input from which to predict the target
+    type Features;
+
+    /// Predicts a target for `input` based on `coefficients`
+    fn predict<N: Number>(&self, coefficients: &[N], input: &Self::Features) -> N;
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Dual, Number};
+
+    // Approximates the derivative of `f` at `x`
+    fn approx_derivate(f: impl Fn(f64) -> f64, x: f64) -> f64 {
+        let epsilon = 0.00001;
+        (f(x + epsilon) - f(x - epsilon)) / (2.0 * epsilon)
+    }
+
+    fn check<F>(f: F, x: f64)
+    where
+        F: Fn(Dual) -> Dual,
+    {
+        let y = f(Dual::variable(x));
+        let approx = approx_derivate(|x| f(Dual::constant(x)).value, x);
+        println!("derivation: {}, approximation: {}", y.derivative, approx);
+        assert!((y.derivative - approx).abs() < 0.0001);
+    }
+
+    #[test]
+    fn arithmetic() {
+        check(|x| x + x * x, 1.5);
+        check(|x| 3.0 - x / (x + 2.0), 1.5);
+        check(|x| 2.0 / x - x * 4.0, 0.7);
+        check(|x| -x + 1.0, 0.7);
+    }
+
+    #[test]
+    fn elementary_functions() {
+        check(|x| 1.0 / (1.0 + x.exp()), 0.3);
+        check(|x| x.ln() * x.sqrt(), 2.3);
+        check(|x| x.powi(3) + x.powf(2.5), 1.3);
+        check(|x| x.sin() * x.cos(), 0.4);
+        check(|x| x.tanh().abs(), -0.4);
+    }
+
+    #[test]
+    fn constant_has_no_derivative() {
+        let y = Dual::constant(2.0).exp() * 4.0;
+        assert_eq!(0.0, y.derivative);
+    }
+}
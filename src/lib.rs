@@ -5,6 +5,7 @@
 //! want to have a look at the [tutorial](./tutorial/index.html).
 
 #![warn(missing_docs)]
+use crate::linear_algebra::Vector;
 use std::iter::IntoIterator;
 
 /// A parameterized expert algorithm
@@ -29,6 +30,16 @@ pub trait Model {
 
     /// Value predict derived by the n-th `coefficient` at `input`
     fn gradient(&self, coefficient: usize, input: &Self::Features) -> Self::Target;
+
+    /// Value predict derived by each coefficient at `input`, if all of them can be computed at once
+    ///
+    /// Models whose derivatives share expensive intermediate results, like the activations of a
+    /// neural network, should implement this. Teachers then compute the gradient once per event,
+    /// before changing any coefficient. By default `None` is returned and teachers call `gradient`
+    /// for each coefficient, right before changing it.
+    fn gradients(&self, _input: &Self::Features) -> Option<Vec<Self::Target>> {
+        None
+    }
 }
 
 /// Representing a cost function whose value is supposed be minimized by the training algorithm.
@@ -51,6 +62,37 @@ pub trait Cost<Truth, Target = f64> {
     fn cost(&self, prediction: Target, truth: Truth) -> f64;
 }
 
+impl<Truth, Target, C> Cost<Truth, Target> for &C
+where
+    C: Cost<Truth, Target>,
+{
+    fn outer_derivative(&self, prediction: &Target, truth: Truth) -> Target {
+        (*self).outer_derivative(prediction, truth)
+    }
+
+    fn cost(&self, prediction: Target, truth: Truth) -> f64 {
+        (*self).cost(prediction, truth)
+    }
+}
+
+/// Cost function which is twice differentiable with respect to the prediction
+///
+/// Second order methods like `solve::irls` use the curvature of the cost function to choose their
+/// steps.
+pub trait SecondDerivative<Truth>: Cost<Truth> {
+    /// Second derivative of the cost function with respect to the prediction
+    fn second_derivative(&self, prediction: f64, truth: Truth) -> f64;
+}
+
+impl<Truth, C> SecondDerivative<Truth> for &C
+where
+    C: SecondDerivative<Truth>,
+{
+    fn second_derivative(&self, prediction: f64, truth: Truth) -> f64 {
+        (*self).second_derivative(prediction, truth)
+    }
+}
+
 /// Algorithms used to adapt [Model](./trait.Model.html) coefficients
 pub trait Teacher<M: Model> {
     /// Contains state which changes during the training, but is not part of the expertise
@@ -73,6 +115,26 @@ pub trait Teacher<M: Model> {
     ) where
         C: Cost<Y, M::Target>,
         Y: Copy;
+
+    /// Like `teach_event`, but scales the influence of the event on the `model` by `weight`
+    ///
+    /// The default implementation multiplies the gradient of the `cost` function with `weight`.
+    fn teach_weighted_event<Y, C>(
+        &self,
+        training: &mut Self::Training,
+        model: &mut M,
+        cost: &C,
+        features: &M::Features,
+        truth: Y,
+        weight: f64,
+    ) where
+        C: Cost<Y, M::Target>,
+        Y: Copy,
+        M::Target: Vector,
+    {
+        let cost = cost::Weighted { cost, weight };
+        self.teach_event(training, model, &cost, features, truth)
+    }
 }
 
 /// Define this trait over the target type of a classifier, to convert it into its truth type
@@ -106,10 +168,102 @@ where
         teacher.teach_event(&mut training, model, cost, &features, truth);
     }
 }
+
+/// Teaches `model` all events in `history`, stopping at the first error
+///
+/// Useful for histories read from files, like `dataset::CsvHistory`. Events before the error
+/// have already been taught to `model` once it is returned.
+pub fn try_learn_history<M, C, T, H, Truth, E>(
+    teacher: &T,
+    cost: &C,
+    model: &mut M,
+    history: H,
+) -> Result<(), E>
+where
+    M: Model,
+    C: Cost<Truth, M::Target>,
+    T: Teacher<M>,
+    H: IntoIterator<Item = Result<(M::Features, Truth), E>>,
+    Truth: Copy,
+{
+    let mut training = teacher.new_training(model);
+    for event in history {
+        let (features, truth) = event?;
+        teacher.teach_event(&mut training, model, cost, &features, truth);
+    }
+    Ok(())
+}
+
+/// Teaches `model` all events in `history`, scaling the influence of each event by its weight
+///
+/// Each event in `history` is a tuple of features, truth and weight.
+pub fn learn_weighted_history<M, C, T, H, Truth>(teacher: &T, cost: &C, model: &mut M, history: H)
+where
+    M: Model,
+    M::Target: Vector,
+    C: Cost<Truth, M::Target>,
+    T: Teacher<M>,
+    H: IntoIterator<Item = (M::Features, Truth, f64)>,
+    Truth: Copy,
+{
+    let mut training = teacher.new_training(model);
+    for (features, truth, weight) in history {
+        teacher.teach_weighted_event(&mut training, model, cost, &features, truth, weight);
+    }
+}
+
+/// Average value of the `cost` function for the predictions of `model` over all events in `history`
+pub fn mean_cost<M, C, H, Truth>(model: &M, cost: &C, history: H) -> f64
+where
+    M: Model,
+    C: Cost<Truth, M::Target>,
+    H: IntoIterator<Item = (M::Features, Truth)>,
+{
+    weighted_mean_cost(
+        model,
+        cost,
+        history
+            .into_iter()
+            .map(|(features, truth)| (features, truth, 1.0)),
+    )
+}
+
+/// Weighted average value of the `cost` function for the predictions of `model` over all events in
+/// `history`
+///
+/// Each event in `history` is a tuple of features, truth and weight. Returns `NaN` if the sum of
+/// all weights is zero.
+pub fn weighted_mean_cost<M, C, H, Truth>(model: &M, cost: &C, history: H) -> f64
+where
+    M: Model,
+    C: Cost<Truth, M::Target>,
+    H: IntoIterator<Item = (M::Features, Truth, f64)>,
+{
+    let (total, sum_of_weights) = history.into_iter().fold(
+        (0.0, 0.0),
+        |(total, sum_of_weights), (features, truth, weight)| {
+            (
+                total + weight * cost.cost(model.predict(&features), truth),
+                sum_of_weights + weight,
+            )
+        },
+    );
+    total / sum_of_weights
+}
 mod array;
 pub mod cost;
 pub mod crisp;
+pub mod dataset;
+pub mod dual;
 pub mod linear_algebra;
+pub mod merge;
 pub mod model;
+pub mod onnx;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod persist;
+pub mod pmml;
+pub mod solve;
 pub mod teacher;
+pub mod transform;
 pub mod tutorial;
@@ -1,5 +1,7 @@
 //! Defines linear algebra traits used for some model parameters
 
+use serde_derive::{Deserialize, Serialize};
+
 /// Vector whose dimension is known at runtime
 ///
 /// Assumes the `Vector` is represented as a tuple of numbers representing its projection along
@@ -10,6 +12,13 @@ pub trait Vector: Clone {
     /// Not every possible implementation knows its dimension at compiletime, therefore a size hint
     /// is necessary to allocate the correct number of elements
     fn zero_from_dimension(dimension: usize) -> Self;
+    /// Dimension of every instance, if it is known at compile time
+    ///
+    /// `zero_from_dimension` panics for any other dimension. The default implementation returns
+    /// `None`, for vectors with a dimension chosen at runtime.
+    fn fixed_dimension() -> Option<usize> {
+        None
+    }
     /// Maximum allowed index for `at` and `at_mut`
     fn dimension(&self) -> usize;
     /// Length of projection along `i`-th base
@@ -41,6 +50,10 @@ impl Vector for f64 {
         0.0
     }
 
+    fn fixed_dimension() -> Option<usize> {
+        Some(1)
+    }
+
     fn dimension(&self) -> usize {
         1
     }
@@ -90,6 +103,10 @@ macro_rules! vec_impl_for_array {
                 [0.0; $v]
             }
 
+            fn fixed_dimension() -> Option<usize> {
+                Some($v)
+            }
+
             fn dimension(&self) -> usize {
                 $v
             }
@@ -144,6 +161,147 @@ vec_impl_for_array! { 30 }
 vec_impl_for_array! { 31 }
 vec_impl_for_array! { 32 }
 
+/// Vector storing only its non-zero elements
+///
+/// Elements are kept as pairs of index and value, ordered by index. Reading an element which is
+/// not stored yields zero, mutable access inserts it. Used for high dimensional data with few
+/// non-zero features, like datasets in the LIBSVM format.
+///
+/// # Example
+///
+/// ```
+/// use vikos::linear_algebra::{SparseVector, Vector};
+///
+/// let mut v = SparseVector::zero_from_dimension(1000);
+/// *v.at_mut(42) = 2.0;
+/// assert_eq!(2.0, v.at(42));
+/// assert_eq!(0.0, v.at(7));
+/// assert_eq!(1, v.entries().len());
+/// ```
+///
+/// Vectors are compared by value, so an explicitly stored zero equals a missing element.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SparseVector {
+    dimension: usize,
+    entries: Vec<(usize, f64)>,
+}
+
+impl SparseVector {
+    /// Creates a vector from pairs of index and value
+    ///
+    /// Panics if an index is not smaller than `dimension`. If an index occurs more than once, the
+    /// last value wins.
+    pub fn from_entries<I>(dimension: usize, entries: I) -> SparseVector
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let mut vector = SparseVector::zero_from_dimension(dimension);
+        for (index, value) in entries {
+            *vector.at_mut(index) = value;
+        }
+        vector
+    }
+
+    /// Stored elements as pairs of index and value, ordered by index
+    pub fn entries(&self) -> &[(usize, f64)] {
+        &self.entries
+    }
+}
+
+impl PartialEq for SparseVector {
+    fn eq(&self, other: &SparseVector) -> bool {
+        if self.dimension != other.dimension {
+            return false;
+        }
+        let (mut a, mut b) = (
+            self.entries.iter().peekable(),
+            other.entries.iter().peekable(),
+        );
+        loop {
+            match (a.peek(), b.peek()) {
+                (None, None) => return true,
+                (Some(&&(i, x)), Some(&&(j, y))) if i == j => {
+                    if x != y {
+                        return false;
+                    }
+                    a.next();
+                    b.next();
+                }
+                // The element with the smaller index is missing in the other vector
+                (Some(&&(i, x)), Some(&&(j, _))) if i < j => {
+                    if x != 0.0 {
+                        return false;
+                    }
+                    a.next();
+                }
+                (Some(&&(_, x)), None) => {
+                    if x != 0.0 {
+                        return false;
+                    }
+                    a.next();
+                }
+                (_, Some(&&(_, y))) => {
+                    if y != 0.0 {
+                        return false;
+                    }
+                    b.next();
+                }
+            }
+        }
+    }
+}
+
+impl Vector for SparseVector {
+    fn zero_from_dimension(dimension: usize) -> SparseVector {
+        SparseVector {
+            dimension,
+            entries: Vec::new(),
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn at(&self, index: usize) -> f64 {
+        assert!(index < self.dimension);
+        match self.entries.binary_search_by_key(&index, |&(i, _)| i) {
+            Ok(position) => self.entries[position].1,
+            Err(_) => 0.0,
+        }
+    }
+
+    fn at_mut(&mut self, index: usize) -> &mut f64 {
+        assert!(index < self.dimension);
+        let position = match self.entries.binary_search_by_key(&index, |&(i, _)| i) {
+            Ok(position) => position,
+            Err(position) => {
+                self.entries.insert(position, (index, 0.0));
+                position
+            }
+        };
+        &mut self.entries[position].1
+    }
+
+    fn dot(&self, other: &Self) -> f64 {
+        debug_assert_eq!(self.dimension(), other.dimension());
+        let mut result = 0.0;
+        let mut rhs = other.entries.iter().peekable();
+        for &(i, a) in &self.entries {
+            while let Some(&&(j, b)) = rhs.peek() {
+                if j > i {
+                    break;
+                }
+                if j == i {
+                    result += a * b;
+                }
+                rhs.next();
+            }
+        }
+        result
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -156,4 +314,33 @@ mod tests {
 
         assert_eq!(11.0, a.dot(&b))
     }
+
+    #[test]
+    fn sparse_dot() {
+        use crate::linear_algebra::{SparseVector, Vector};
+
+        let a = SparseVector::from_entries(10, vec![(1, 2.0), (4, 3.0), (9, 1.0)]);
+        let b = SparseVector::from_entries(10, vec![(9, 5.0), (0, 7.0), (4, -1.0)]);
+
+        assert_eq!(vec![(0, 7.0), (4, -1.0), (9, 5.0)], b.entries());
+        assert_eq!(2.0, a.dot(&b));
+        assert_eq!(a.dot(&b), b.dot(&a));
+    }
+
+    #[test]
+    fn sparse_eq() {
+        use crate::linear_algebra::{SparseVector, Vector};
+
+        let a = SparseVector::from_entries(10, vec![(1, 2.0), (4, 3.0)]);
+        let mut b = a.clone();
+        *b.at_mut(0) = 0.0;
+        *b.at_mut(9) = 0.0;
+        assert_eq!(a, b);
+        assert_eq!(b, a);
+        *b.at_mut(9) = 1.0;
+        assert_ne!(a, b);
+        assert_ne!(b, a);
+        assert_ne!(a, SparseVector::from_entries(11, vec![(1, 2.0), (4, 3.0)]));
+        assert_ne!(a, SparseVector::from_entries(10, vec![(1, 2.0), (4, 1.0)]));
+    }
 }
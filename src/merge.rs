@@ -0,0 +1,241 @@
+//! Combining models trained separately
+//!
+//! Models of the same shape, e.g. trained on different regions or in different processes, can be
+//! merged by averaging their coefficients. `iterative_parameter_mixing` repeats this after each
+//! epoch of training on separate shards of a history.
+
+use crate::{Cost, Model, Teacher};
+use std::{error, fmt};
+
+/// Errors occurring while merging models
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// No models have been passed
+    Empty,
+    /// A model has a different number of coefficients than the first one
+    CoefficientMismatch {
+        /// Position of the model
+        position: usize,
+        /// Number of coefficients of the first model
+        expected: usize,
+        /// Number of coefficients of the model at `position`
+        found: usize,
+    },
+    /// A weight is negative or not finite, or all weights are zero
+    InvalidWeight(f64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "no models to merge"),
+            Error::CoefficientMismatch {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "model {} has {} coefficients, expected {}",
+                position, found, expected
+            ),
+            Error::InvalidWeight(weight) => write!(f, "invalid weight {}", weight),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Model whose coefficients are the mean of the coefficients of all `models`
+///
+/// All models must have the same number of coefficients. Parts of the model which are not
+/// coefficients are taken from the first model.
+///
+/// # Example
+///
+/// ```
+/// use vikos::merge::average;
+/// use vikos::model::Linear;
+///
+/// let north = Linear { m: [1.0], c: 2.0 };
+/// let south = Linear { m: [3.0], c: 0.0 };
+/// let merged = average(vec![north, south]).unwrap();
+/// assert_eq!([2.0], merged.m);
+/// assert_eq!(1.0, merged.c);
+/// ```
+pub fn average<M, I>(models: I) -> Result<M, Error>
+where
+    M: Model,
+    I: IntoIterator<Item = M>,
+{
+    weighted_average(models.into_iter().map(|model| (model, 1.0)))
+}
+
+/// Model whose coefficients are the weighted mean of the coefficients of all `models`
+///
+/// Each item is a model together with its weight, e.g. the number of events it has been trained
+/// with. Weights must not be negative and must not all be zero.
+pub fn weighted_average<M, I>(models: I) -> Result<M, Error>
+where
+    M: Model,
+    I: IntoIterator<Item = (M, f64)>,
+{
+    let mut models = models.into_iter();
+    let (mut result, weight) = models.next().ok_or(Error::Empty)?;
+    check_weight(weight)?;
+    let n = result.num_coefficients();
+    let mut sums: Vec<f64> = (0..n).map(|ci| weight * *result.coefficient(ci)).collect();
+    let mut total = weight;
+
+    for (position, (mut model, weight)) in models.enumerate() {
+        if model.num_coefficients() != n {
+            return Err(Error::CoefficientMismatch {
+                position: position + 1,
+                expected: n,
+                found: model.num_coefficients(),
+            });
+        }
+        check_weight(weight)?;
+        for (ci, sum) in sums.iter_mut().enumerate() {
+            *sum += weight * *model.coefficient(ci);
+        }
+        total += weight;
+    }
+
+    if total <= 0.0 {
+        return Err(Error::InvalidWeight(total));
+    }
+    for (ci, sum) in sums.into_iter().enumerate() {
+        *result.coefficient(ci) = sum / total;
+    }
+    Ok(result)
+}
+
+fn check_weight(weight: f64) -> Result<(), Error> {
+    if weight >= 0.0 && weight.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidWeight(weight))
+    }
+}
+
+/// Trains `model` on separate `shards` of a history and mixes the results after each epoch
+///
+/// In each of the `epochs` a copy of `model` is trained on every shard. Afterwards `model` is
+/// replaced by the average of these copies, weighted by the number of events in each shard. The
+/// `Teacher::Training` of each shard is kept between epochs. This is how a parameter server would
+/// combine models trained in separate processes, but everything runs sequentially here.
+///
+/// # Example
+///
+/// ```
+/// use vikos::merge::iterative_parameter_mixing;
+/// use vikos::{cost, model, teacher};
+///
+/// let north = vec![([1.0], 3.0), ([2.0], 5.0)];
+/// let south = vec![([-1.0], -1.0), ([0.0], 1.0), ([3.0], 7.0)];
+/// let mut model = model::Linear::<[f64; 1]>::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.05 };
+/// let cost = cost::LeastSquares {};
+///
+/// iterative_parameter_mixing(&teacher, &cost, &mut model, &[north, south], 300).unwrap();
+/// assert!((model.m[0] - 2.0).abs() < 0.01);
+/// ```
+pub fn iterative_parameter_mixing<M, C, T, S, Truth>(
+    teacher: &T,
+    cost: &C,
+    model: &mut M,
+    shards: &[S],
+    epochs: usize,
+) -> Result<(), Error>
+where
+    M: Model + Clone,
+    C: Cost<Truth, M::Target>,
+    T: Teacher<M>,
+    S: AsRef<[(M::Features, Truth)]>,
+    Truth: Copy,
+{
+    let mut trainings: Vec<_> = shards.iter().map(|_| teacher.new_training(model)).collect();
+    for _ in 0..epochs {
+        let trained = shards
+            .iter()
+            .zip(&mut trainings)
+            .map(|(shard, training)| {
+                let shard = shard.as_ref();
+                let mut local = model.clone();
+                for (features, truth) in shard {
+                    teacher.teach_event(training, &mut local, cost, features, *truth);
+                }
+                (local, shard.len() as f64)
+            })
+            .collect::<Vec<_>>();
+        *model = weighted_average(trained)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{average, weighted_average, Error};
+    use crate::model::{Linear, Logistic};
+    use crate::Model;
+
+    #[test]
+    fn weighted() {
+        let a = Linear {
+            m: [1.0, 0.0],
+            c: 4.0,
+        };
+        let b = Linear {
+            m: [4.0, 2.0],
+            c: 1.0,
+        };
+        let merged = weighted_average(vec![(a, 2.0), (b, 1.0)]).unwrap();
+        assert_eq!([2.0, 2.0 / 3.0], merged.m);
+        assert_eq!(3.0, merged.c);
+    }
+
+    #[test]
+    fn logistic() {
+        let mut a = Logistic::<[f64; 1]>::default();
+        *a.coefficient(0) = 2.0;
+        let b = Logistic::default();
+        let mut merged = average(vec![a, b]).unwrap();
+        assert_eq!(1.0, *merged.coefficient(0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(Error::Empty),
+            average(Vec::<Linear<[f64; 1]>>::new()).map(|_| ())
+        );
+        let mismatch = average(vec![
+            Linear::<Vec<f64>>::with_feature_dimension(2),
+            Linear::with_feature_dimension(2),
+            Linear::with_feature_dimension(3),
+        ])
+        .map(|_| ());
+        assert_eq!(
+            Err(Error::CoefficientMismatch {
+                position: 2,
+                expected: 3,
+                found: 4
+            }),
+            mismatch
+        );
+        assert_eq!(
+            "model 2 has 4 coefficients, expected 3",
+            mismatch.unwrap_err().to_string()
+        );
+        let zero = Linear { m: [1.0], c: 0.0 };
+        assert_eq!(
+            Err(Error::InvalidWeight(0.0)),
+            weighted_average(vec![(zero.clone(), 0.0), (zero.clone(), 0.0)]).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::InvalidWeight(-1.0)),
+            weighted_average(vec![(zero.clone(), 1.0), (zero, -1.0)]).map(|_| ())
+        );
+    }
+}
@@ -2,10 +2,14 @@
 
 use crate::{
     array,
+    dual::{Dual, Function},
     linear_algebra::{FixDimension, Vector},
+    transform::{PolynomialFeatures, Transformer},
     Model,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde_derive::{Deserialize, Serialize};
+use std::marker::PhantomData;
 
 impl Model for f64 {
     type Features = ();
@@ -98,6 +102,463 @@ where
     }
 }
 
+/// Models the target as a polynomial of the features
+///
+/// The features are expanded into all products of up to `degree` features (see
+/// `transform::PolynomialFeatures`), which are then combined by a `Linear` model. The coefficients
+/// of the model are those of the `Linear` model, in the order in which `expand` lists the terms,
+/// followed by the offset.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+///
+/// // y = x0^2 + x0 * x1
+/// let history = [([1.0, 2.0], 3.0), ([2.0, 0.5], 5.0), ([-1.0, 1.0], 0.0)];
+/// let mut model = model::Polynomial::new(2, 2);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.01 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(300).cloned());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Polynomial<V> {
+    /// Expands the features into the terms of the polynomial
+    pub expansion: PolynomialFeatures<V>,
+    /// `Linear` model over the expanded features
+    pub linear: Linear<Vec<f64>>,
+}
+
+impl<V> Polynomial<V> {
+    /// Create a polynomial model of `degree` over features of the specified dimension
+    ///
+    /// All products of up to `degree` features are used, including powers of single features.
+    pub fn new(dimension: usize, degree: usize) -> Self {
+        Self::from_expansion(PolynomialFeatures::new(dimension, degree))
+    }
+
+    /// Create a polynomial model of `degree` without powers of single features
+    ///
+    /// Only products of up to `degree` distinct features are used, e.g. `x0 * x1`, but not
+    /// `x0 * x0`.
+    pub fn interaction_only(dimension: usize, degree: usize) -> Self {
+        Self::from_expansion(PolynomialFeatures::interaction_only(dimension, degree))
+    }
+
+    fn from_expansion(expansion: PolynomialFeatures<V>) -> Self {
+        Polynomial {
+            linear: Linear::with_feature_dimension(expansion.output_dimension()),
+            expansion,
+        }
+    }
+
+    /// Expands `input` into the terms of the polynomial
+    ///
+    /// Terms are ordered by degree first. E.g. for two features and degree two: `x0`, `x1`,
+    /// `x0*x0`, `x0*x1`, `x1*x1`.
+    pub fn expand(&self, input: &V) -> Vec<f64>
+    where
+        V: Vector,
+    {
+        self.expansion.transform(input)
+    }
+}
+
+impl<V> Model for Polynomial<V>
+where
+    V: Vector,
+{
+    type Features = V;
+    type Target = f64;
+
+    fn num_coefficients(&self) -> usize {
+        self.linear.num_coefficients()
+    }
+
+    fn coefficient(&mut self, coefficient: usize) -> &mut f64 {
+        self.linear.coefficient(coefficient)
+    }
+
+    fn predict(&self, input: &V) -> f64 {
+        self.linear.predict(&self.expand(input))
+    }
+
+    fn gradient(&self, coefficient: usize, input: &V) -> f64 {
+        self.linear.gradient(coefficient, &self.expand(input))
+    }
+
+    fn gradients(&self, input: &V) -> Option<Vec<f64>> {
+        // Expanding the input once suffices, since the terms are the derivatives of the linear
+        // coefficients
+        let mut gradients = self.expand(input);
+        gradients.push(1.0); // derive by the offset
+        Some(gradients)
+    }
+}
+
+/// Second order factorization machine
+///
+/// Models the target as `y = m * x + c + sum_i sum_j>i <v_i, v_j> x_i x_j`, with `v_i` being a
+/// vector of `k` latent factors for the `i`-th feature. In contrast to a `Polynomial` model, the
+/// weight of the interaction between two features is factorized, so it can also be estimated for
+/// pairs of features which rarely occur together, like sparse one hot encoded categories.
+///
+/// The coefficients of the model are those of `linear` followed by `factors`.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+///
+/// // One hot encoded store (first two features) and product (last two features)
+/// let history = [([1.0, 0.0, 1.0, 0.0], 3.0), ([0.0, 1.0, 0.0, 1.0], 1.0)];
+/// let mut model = model::FactorizationMachine::new(4, 2, 42);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(100).cloned());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactorizationMachine<V> {
+    /// `Linear` term of the model
+    pub linear: Linear<V>,
+    /// Latent factors. The `k` factors of the `i`-th feature are stored at `i * k..(i + 1) * k`
+    pub factors: Vec<f64>,
+    /// Number of latent factors for each feature
+    pub k: usize,
+}
+
+impl<V: Vector> FactorizationMachine<V> {
+    /// Create a factorization machine with `k` latent factors for each feature
+    ///
+    /// The linear term starts at zero. The latent factors are initialized with small random
+    /// values drawn from a generator seeded with `seed`, since all gradients of the factors would
+    /// stay zero otherwise.
+    pub fn new(dimension: usize, k: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        FactorizationMachine {
+            linear: Linear::with_feature_dimension(dimension),
+            factors: (0..dimension * k)
+                .map(|_| rng.gen_range(-0.1..0.1))
+                .collect(),
+            k,
+        }
+    }
+
+    /// Sum of the `f`-th latent factor of all features, weighted with the feature values
+    fn factor_sum(&self, f: usize, input: &V) -> f64 {
+        (0..input.dimension())
+            .map(|i| self.factors[i * self.k + f] * input.at(i))
+            .sum()
+    }
+}
+
+impl<V> Model for FactorizationMachine<V>
+where
+    V: Vector,
+{
+    type Features = V;
+    type Target = f64;
+
+    fn num_coefficients(&self) -> usize {
+        self.linear.num_coefficients() + self.factors.len()
+    }
+
+    fn coefficient(&mut self, coefficient: usize) -> &mut f64 {
+        let num_linear = self.linear.num_coefficients();
+        if coefficient < num_linear {
+            self.linear.coefficient(coefficient)
+        } else {
+            &mut self.factors[coefficient - num_linear]
+        }
+    }
+
+    fn predict(&self, input: &V) -> f64 {
+        // Uses sum_i sum_j>i <v_i, v_j> x_i x_j =
+        // 1/2 sum_f ((sum_i v_if x_i)^2 - sum_i v_if^2 x_i^2)
+        let interactions: f64 = (0..self.k)
+            .map(|f| {
+                let squared: f64 = (0..input.dimension())
+                    .map(|i| (self.factors[i * self.k + f] * input.at(i)).powi(2))
+                    .sum();
+                self.factor_sum(f, input).powi(2) - squared
+            })
+            .sum();
+        self.linear.predict(input) + 0.5 * interactions
+    }
+
+    fn gradient(&self, coefficient: usize, input: &V) -> f64 {
+        let num_linear = self.linear.num_coefficients();
+        if coefficient < num_linear {
+            self.linear.gradient(coefficient, input)
+        } else {
+            let index = coefficient - num_linear;
+            let (i, f) = (index / self.k, index % self.k);
+            let x = input.at(i);
+            x * self.factor_sum(f, input) - self.factors[index] * x * x
+        }
+    }
+
+    fn gradients(&self, input: &V) -> Option<Vec<f64>> {
+        // The sums are shared by the derivatives of all factors
+        let sums: Vec<f64> = (0..self.k).map(|f| self.factor_sum(f, input)).collect();
+        let num_linear = self.linear.num_coefficients();
+        let mut gradients = Vec::with_capacity(self.num_coefficients());
+        gradients.extend((0..num_linear).map(|ci| self.linear.gradient(ci, input)));
+        for i in 0..input.dimension() {
+            let x = input.at(i);
+            let factors = &self.factors[i * self.k..(i + 1) * self.k];
+            gradients.extend(
+                factors
+                    .iter()
+                    .zip(&sums)
+                    .map(|(factor, sum)| x * sum - factor * x * x),
+            );
+        }
+        Some(gradients)
+    }
+}
+
+/// Activation function applied to the outputs of a `Layer`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    /// `f(x) = x`
+    Identity,
+    /// Rectified linear unit `f(x) = max(0, x)`
+    Relu,
+    /// Hyperbolic tangent
+    Tanh,
+    /// `f(x) = 1/(1+e^-x)`
+    Sigmoid,
+}
+
+impl Activation {
+    fn apply(self, x: f64) -> f64 {
+        match self {
+            Activation::Identity => x,
+            Activation::Relu => x.max(0.0),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+        }
+    }
+
+    fn derivative(self, x: f64) -> f64 {
+        match self {
+            Activation::Identity => 1.0,
+            Activation::Relu => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Tanh => 1.0 - x.tanh().powi(2),
+            Activation::Sigmoid => {
+                let s = Activation::Sigmoid.apply(x);
+                s * (1.0 - s)
+            }
+        }
+    }
+}
+
+/// Fully connected layer of a multilayer perceptron
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Layer {
+    /// Weight of the connection from input `i` to output `j` is stored at `j * inputs + i`
+    pub weights: Vec<f64>,
+    /// One bias for each output
+    pub biases: Vec<f64>,
+    /// Applied to each output
+    pub activation: Activation,
+}
+
+impl Layer {
+    fn num_coefficients(&self) -> usize {
+        self.weights.len() + self.biases.len()
+    }
+
+    /// Outputs of the layer before the activation function is applied
+    fn weighted_sums(&self, inputs: &[f64]) -> Vec<f64> {
+        assert_eq!(
+            self.weights.len(),
+            inputs.len() * self.biases.len(),
+            "Number of inputs does not match the weights of the layer"
+        );
+        self.biases
+            .iter()
+            .enumerate()
+            .map(|(j, bias)| {
+                let row = &self.weights[j * inputs.len()..(j + 1) * inputs.len()];
+                row.iter().zip(inputs).map(|(w, x)| w * x).sum::<f64>() + bias
+            })
+            .collect()
+    }
+}
+
+/// Multilayer perceptron with a single output
+///
+/// Consists of fully connected `layers`. The coefficients of the model are the weights followed by
+/// the biases of each layer, starting with the layer connected to the input.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+/// use vikos::model::Activation;
+///
+/// let history = [([0.0, 0.0], false), ([0.0, 1.0], true), ([1.0, 0.0], true)];
+/// let mut model = model::Mlp::new(2, &[(4, Activation::Tanh)], Activation::Sigmoid, 42);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+/// let cost = cost::MaxLikelihood {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(30).cloned());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mlp<V> {
+    /// Layers of the perceptron, starting with the one connected to the input
+    pub layers: Vec<Layer>,
+    features: PhantomData<V>,
+}
+
+impl<V> Mlp<V> {
+    /// Create a multilayer perceptron with randomly initialized weights
+    ///
+    /// `hidden` lists size and activation of each hidden layer. The single output is passed
+    /// through the `output` activation, e.g. `Activation::Sigmoid` for a binary classifier. Weights
+    /// are drawn uniformly from `[-a, a]` with `a = sqrt(6 / (inputs + outputs))` of each layer,
+    /// using a random generator seeded with `seed`. All biases start at zero.
+    ///
+    /// # Panics
+    ///
+    /// If a hidden layer has a size of zero.
+    pub fn new(
+        dimension: usize,
+        hidden: &[(usize, Activation)],
+        output: Activation,
+        seed: u64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut inputs = dimension;
+        let layers = hidden
+            .iter()
+            .cloned()
+            .chain(Some((1, output)))
+            .map(|(outputs, activation)| {
+                assert!(outputs > 0, "Layers of a perceptron must not be empty");
+                let limit = (6.0 / (inputs + outputs) as f64).sqrt();
+                let layer = Layer {
+                    weights: (0..inputs * outputs)
+                        .map(|_| rng.gen_range(-limit..limit))
+                        .collect(),
+                    biases: vec![0.0; outputs],
+                    activation,
+                };
+                inputs = outputs;
+                layer
+            })
+            .collect();
+        Mlp {
+            layers,
+            features: PhantomData,
+        }
+    }
+
+    /// Weighted sums and outputs of each layer. The first output is the input of the model
+    fn forward(&self, input: &V) -> (Vec<Vec<f64>>, Vec<Vec<f64>>)
+    where
+        V: Vector,
+    {
+        let mut sums = Vec::with_capacity(self.layers.len());
+        let mut outputs = vec![(0..input.dimension())
+            .map(|i| input.at(i))
+            .collect::<Vec<_>>()];
+        for layer in &self.layers {
+            let z = layer.weighted_sums(outputs.last().unwrap());
+            outputs.push(z.iter().map(|&x| layer.activation.apply(x)).collect());
+            sums.push(z);
+        }
+        (sums, outputs)
+    }
+
+    /// Derivatives of the output by all coefficients, using a single forward and backward pass
+    fn backpropagate(&self, input: &V) -> Vec<f64>
+    where
+        V: Vector,
+    {
+        let (sums, outputs) = self.forward(input);
+        // Derivative of the output with respect to the weighted sums of the current layer,
+        // starting with the output layer
+        let last = self.layers.len() - 1;
+        let mut delta = vec![self.layers[last].activation.derivative(sums[last][0])];
+        let mut gradients: Vec<Vec<f64>> = Vec::with_capacity(self.layers.len());
+        for l in (0..=last).rev() {
+            let layer = &self.layers[l];
+            let inputs = &outputs[l];
+            let mut gradient = Vec::with_capacity(layer.num_coefficients());
+            for d in &delta {
+                gradient.extend(inputs.iter().map(|x| d * x));
+            }
+            gradient.extend_from_slice(&delta);
+            gradients.push(gradient);
+
+            if l > 0 {
+                let activation = self.layers[l - 1].activation;
+                delta = (0..inputs.len())
+                    .map(|i| {
+                        let propagated: f64 = delta
+                            .iter()
+                            .enumerate()
+                            .map(|(j, d)| layer.weights[j * inputs.len() + i] * d)
+                            .sum();
+                        activation.derivative(sums[l - 1][i]) * propagated
+                    })
+                    .collect();
+            }
+        }
+        gradients.into_iter().rev().flatten().collect()
+    }
+}
+
+impl<V> Model for Mlp<V>
+where
+    V: Vector,
+{
+    type Features = V;
+    type Target = f64;
+
+    fn num_coefficients(&self) -> usize {
+        self.layers.iter().map(Layer::num_coefficients).sum()
+    }
+
+    fn coefficient(&mut self, mut coefficient: usize) -> &mut f64 {
+        for layer in &mut self.layers {
+            if coefficient < layer.weights.len() {
+                return &mut layer.weights[coefficient];
+            }
+            coefficient -= layer.weights.len();
+            if coefficient < layer.biases.len() {
+                return &mut layer.biases[coefficient];
+            }
+            coefficient -= layer.biases.len();
+        }
+        panic!("coefficient index out of range")
+    }
+
+    fn predict(&self, input: &V) -> f64 {
+        let (_, outputs) = self.forward(input);
+        outputs.last().unwrap()[0]
+    }
+
+    fn gradient(&self, coefficient: usize, input: &V) -> f64 {
+        self.backpropagate(input)[coefficient]
+    }
+
+    fn gradients(&self, input: &V) -> Option<Vec<f64>> {
+        Some(self.backpropagate(input))
+    }
+}
+
 /// Models target as `y = 1/(1+e^(m * x + c))`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Logistic<V>(Linear<V>);
@@ -110,6 +571,13 @@ impl<V: Vector> Logistic<V> {
     }
 }
 
+impl<V> Logistic<V> {
+    /// The linear term `m * x + c`
+    pub fn linear(&self) -> &Linear<V> {
+        &self.0
+    }
+}
+
 impl<V> Default for Logistic<V>
 where
     V: FixDimension,
@@ -146,6 +614,8 @@ where
 
 /// Models the target as `y = g(m*x + c)`
 ///
+/// `g` is evaluated using dual numbers, so its derivative is obtained automatically.
+///
 /// # Example
 ///
 /// Logistic regression implemented using a generalized linear model. This is just for
@@ -153,9 +623,9 @@ where
 ///
 /// ```
 /// # use vikos::{model, teacher, cost, learn_history};
+/// use vikos::dual::Number;
 /// # let history = [(0.0, true)];
-/// let mut model = model::GeneralizedLinearModel::new(|x| 1.0 / (1.0 + x.exp()),
-///                                                    |x| -x.exp() / (1.0 + x.exp()).powi(2) );
+/// let mut model = model::GeneralizedLinearModel::new(|x| 1.0 / (1.0 + x.exp()));
 /// let teacher = teacher::GradientDescent { learning_rate: 0.3 };
 /// let cost = cost::MaxLikelihood {};
 ///
@@ -165,37 +635,32 @@ where
 ///               history.iter().cloned());
 /// ```
 #[derive(Clone)]
-pub struct GeneralizedLinearModel<V, G, Dg> {
+pub struct GeneralizedLinearModel<V, G> {
     /// `Linear` term of the generalized linear `Model`
     pub linear: Linear<V>,
     /// Outer function applied to the result of `linear`
     pub g: G,
-    /// Derivation of `g`
-    pub g_derivate: Dg,
 }
 
-impl<V, G, Dg> GeneralizedLinearModel<V, G, Dg>
+impl<V, G> GeneralizedLinearModel<V, G>
 where
-    G: Fn(f64) -> f64,
-    Dg: Fn(f64) -> f64,
+    G: Fn(Dual) -> Dual,
 {
     /// Creates new model with the coefficients set to zero
-    pub fn new(g: G, g_derivate: Dg) -> GeneralizedLinearModel<V, G, Dg>
+    pub fn new(g: G) -> GeneralizedLinearModel<V, G>
     where
         V: FixDimension,
     {
         GeneralizedLinearModel {
             linear: Linear::default(),
             g,
-            g_derivate,
         }
     }
 }
 
-impl<V, F, Df> Model for GeneralizedLinearModel<V, F, Df>
+impl<V, G> Model for GeneralizedLinearModel<V, G>
 where
-    F: Fn(f64) -> f64,
-    Df: Fn(f64) -> f64,
+    G: Fn(Dual) -> Dual,
     Linear<V>: Model<Features = V, Target = f64>,
 {
     type Features = V;
@@ -211,12 +676,175 @@ where
 
     fn predict(&self, input: &V) -> f64 {
         let f = &self.g;
-        f(self.linear.predict(&input))
+        f(Dual::constant(self.linear.predict(input))).value
     }
 
     fn gradient(&self, coefficient: usize, input: &V) -> f64 {
-        let f = &self.g_derivate;
-        f(self.linear.predict(&input)) * self.linear.gradient(coefficient, input)
+        let f = &self.g;
+        f(Dual::variable(self.linear.predict(input))).derivative
+            * self.linear.gradient(coefficient, input)
+    }
+}
+
+/// A `Model` whose gradient is derived automatically from its prediction
+///
+/// The prediction is defined by implementing `dual::Function` generically over `dual::Number`.
+/// The gradient is then calculated by evaluating the function with dual numbers.
+///
+/// Forward mode differentiation yields the derivative by a single coefficient per evaluation. A
+/// gradient therefore costs one evaluation of the function for each coefficient, i.e. teaching an
+/// event with `n` coefficients takes `n` evaluations of `predict`, each of which usually touches
+/// all `n` coefficients. This is fine for models with few coefficients. Implement `Model` by hand
+/// for larger ones.
+///
+/// # Example
+///
+/// Models the target as `y = a * e^(b * x)`
+///
+/// ```
+/// use vikos::dual::{Function, Number};
+/// use vikos::{cost, learn_history, model, teacher};
+///
+/// struct Exponential;
+///
+/// impl Function for Exponential {
+///     type Features = f64;
+///
+///     fn predict<N: Number>(&self, coefficients: &[N], input: &f64) -> N {
+///         coefficients[0] * (coefficients[1] * *input).exp()
+///     }
+/// }
+///
+/// # let history = [(0.0, 2.0), (1.0, 5.4)];
+/// let mut model = model::AutoDiff::new(Exponential, 2);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.01 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cloned());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoDiff<F> {
+    /// Coefficients passed to `function`
+    pub coefficients: Vec<f64>,
+    /// Defines the prediction of the model
+    pub function: F,
+}
+
+impl<F> AutoDiff<F> {
+    /// Creates a new model with `num_coefficients` coefficients set to zero
+    pub fn new(function: F, num_coefficients: usize) -> Self {
+        AutoDiff {
+            coefficients: vec![0.0; num_coefficients],
+            function,
+        }
+    }
+}
+
+impl<F> Model for AutoDiff<F>
+where
+    F: Function,
+{
+    type Features = F::Features;
+    type Target = f64;
+
+    fn num_coefficients(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    fn coefficient(&mut self, coefficient: usize) -> &mut f64 {
+        &mut self.coefficients[coefficient]
+    }
+
+    fn predict(&self, input: &F::Features) -> f64 {
+        self.function.predict(&self.coefficients, input)
+    }
+
+    fn gradient(&self, coefficient: usize, input: &F::Features) -> f64 {
+        let coefficients: Vec<_> = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(ci, &value)| {
+                if ci == coefficient {
+                    Dual::variable(value)
+                } else {
+                    Dual::constant(value)
+                }
+            })
+            .collect();
+        self.function.predict(&coefficients, input).derivative
+    }
+}
+
+/// Transforms the input before passing it to the inner `model`
+///
+/// Bundles a feature transformation (e.g. a `transform::StandardScaler`) with a model, so the
+/// same transformation is applied during training and prediction. The coefficients are those of
+/// the inner `model`, so every `Teacher` can be used to train the pipeline. Several transformations
+/// are chained by passing them as a tuple, which applies the first element first.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+/// use vikos::transform::{PolynomialFeatures, StandardScaler};
+///
+/// let history = [([1.0, 200.0], 3.0), ([3.0, 400.0], 5.0), ([2.0, 100.0], 2.0)];
+/// let mut model = model::Pipeline {
+///     transformer: (
+///         StandardScaler::fit(2, history.iter().map(|(x, _)| x)),
+///         PolynomialFeatures::new(2, 2),
+///     ),
+///     model: model::Linear::with_feature_dimension(5),
+/// };
+/// let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cycle().take(30).cloned());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Pipeline<T, M> {
+    /// Applied to the input before it is passed to `model`
+    pub transformer: T,
+    /// Model predicting the target from the transformed input
+    pub model: M,
+}
+
+impl<T, M> Model for Pipeline<T, M>
+where
+    T: Transformer<Output = M::Features>,
+    M: Model,
+{
+    type Features = T::Input;
+    type Target = M::Target;
+
+    fn num_coefficients(&self) -> usize {
+        self.model.num_coefficients()
+    }
+
+    fn coefficient(&mut self, coefficient: usize) -> &mut f64 {
+        self.model.coefficient(coefficient)
+    }
+
+    fn predict(&self, input: &T::Input) -> M::Target {
+        self.model.predict(&self.transformer.transform(input))
+    }
+
+    fn gradient(&self, coefficient: usize, input: &T::Input) -> M::Target {
+        self.model
+            .gradient(coefficient, &self.transformer.transform(input))
+    }
+
+    /// Transforms `input` only once for all coefficients
+    fn gradients(&self, input: &T::Input) -> Option<Vec<M::Target>> {
+        let features = self.transformer.transform(input);
+        self.model.gradients(&features).or_else(|| {
+            Some(
+                (0..self.model.num_coefficients())
+                    .map(|ci| self.model.gradient(ci, &features))
+                    .collect(),
+            )
+        })
     }
 }
 
@@ -233,6 +861,11 @@ impl<T> OneVsRest<T> {
     pub fn new(t: T) -> Self {
         OneVsRest(t)
     }
+
+    /// The binary classifiers, one for each class
+    pub fn models(&self) -> &T {
+        &self.0
+    }
 }
 
 impl<T> Model for OneVsRest<T>
@@ -276,3 +909,266 @@ where
         result
     }
 }
+
+/// Compares `Model::gradient` with a finite difference approximation
+///
+/// Each coefficient of `model` is shifted by `epsilon` in both directions to approximate the
+/// derivative of `predict` at `input` by the central difference quotient. Returns for each
+/// coefficient the absolute difference between `gradient` and the approximation (the maximum over
+/// all elements for vector valued targets). Useful to test the `gradient` of custom models.
+///
+/// `model` is mutated during the check, but all coefficients are restored before returning.
+///
+/// # Example
+///
+/// ```
+/// use vikos::model::{check_gradient, Logistic};
+/// use vikos::Model;
+///
+/// let mut model = Logistic::default();
+/// *model.coefficient(0) = 0.3;
+/// let mismatch = check_gradient(&mut model, &[1.5, -2.0], 1e-6);
+/// assert!(mismatch.iter().all(|&m| m < 1e-6));
+/// ```
+pub fn check_gradient<M>(model: &mut M, input: &M::Features, epsilon: f64) -> Vec<f64>
+where
+    M: Model,
+    M::Target: Vector,
+{
+    (0..model.num_coefficients())
+        .map(|ci| {
+            let gradient = model.gradient(ci, input);
+            let original = *model.coefficient(ci);
+            *model.coefficient(ci) = original + epsilon;
+            let plus_epsilon = model.predict(input);
+            *model.coefficient(ci) = original - epsilon;
+            let minus_epsilon = model.predict(input);
+            *model.coefficient(ci) = original;
+            (0..gradient.dimension())
+                .map(|i| {
+                    let approx = (plus_epsilon.at(i) - minus_epsilon.at(i)) / (2.0 * epsilon);
+                    (gradient.at(i) - approx).abs()
+                })
+                .fold(0.0, f64::max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        check_gradient, Activation, AutoDiff, FactorizationMachine, GeneralizedLinearModel, Linear,
+        Logistic, Mlp, OneVsRest, Pipeline, Polynomial,
+    };
+    use crate::{
+        dual::{Function, Number},
+        linear_algebra::Vector,
+        transform::StandardScaler,
+        Model,
+    };
+
+    // Sets all coefficients to distinct, non zero values
+    fn init<M: Model>(model: &mut M) {
+        for ci in 0..model.num_coefficients() {
+            *model.coefficient(ci) = 0.1 * (ci as f64 + 1.0) * if ci % 2 == 0 { 1.0 } else { -1.0 };
+        }
+    }
+
+    fn assert_gradient<M>(model: &mut M, input: &M::Features)
+    where
+        M: Model,
+        M::Target: Vector,
+    {
+        init(model);
+        let mismatch = check_gradient(model, input, 1e-6);
+        assert_eq!(model.num_coefficients(), mismatch.len());
+        assert!(mismatch.iter().all(|&m| m < 1e-6));
+
+        // Derivatives computed at once must match the ones of the single coefficients
+        if let Some(gradients) = model.gradients(input) {
+            assert_eq!(model.num_coefficients(), gradients.len());
+            for (ci, g) in gradients.iter().enumerate() {
+                let single = model.gradient(ci, input);
+                for i in 0..g.dimension() {
+                    assert!((g.at(i) - single.at(i)).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_gradient() {
+        assert_gradient(&mut Linear::<[f64; 3]>::default(), &[1.0, -2.0, 0.5]);
+        assert_gradient(&mut Linear::with_feature_dimension(2), &vec![3.0, 0.7]);
+    }
+
+    #[test]
+    fn polynomial_gradient() {
+        assert_gradient(&mut Polynomial::new(3, 3), &[1.0, -2.0, 0.5]);
+        assert_gradient(
+            &mut Polynomial::interaction_only(3, 2),
+            &vec![1.0, -2.0, 0.5],
+        );
+    }
+
+    #[test]
+    fn polynomial_expansion() {
+        let model = Polynomial::new(2, 2);
+        assert_eq!(vec![2.0, 3.0, 4.0, 6.0, 9.0], model.expand(&[2.0, 3.0]));
+        assert_eq!(6, model.num_coefficients());
+
+        let model = Polynomial::interaction_only(3, 3);
+        assert_eq!(
+            vec![2.0, 3.0, 5.0, 6.0, 10.0, 15.0, 30.0],
+            model.expand(&[2.0, 3.0, 5.0])
+        );
+    }
+
+    #[test]
+    fn factorization_machine_gradient() {
+        assert_gradient(&mut FactorizationMachine::new(3, 2, 7), &[1.0, -2.0, 0.5]);
+        assert_gradient(
+            &mut FactorizationMachine::new(4, 3, 7),
+            &vec![1.0, 0.0, 0.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn factorization_machine_predict() {
+        let model = FactorizationMachine {
+            linear: Linear {
+                m: [1.0, 0.0, 2.0],
+                c: 0.5,
+            },
+            factors: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            k: 2,
+        };
+        let x = [1.0, 2.0, 3.0];
+        // Pairwise interactions computed naively
+        let mut expected = 0.5 + 1.0 + 6.0;
+        for i in 0..3 {
+            for j in i + 1..3 {
+                let dot = model.factors[2 * i] * model.factors[2 * j]
+                    + model.factors[2 * i + 1] * model.factors[2 * j + 1];
+                expected += dot * x[i] * x[j];
+            }
+        }
+        assert!((model.predict(&x) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mlp_gradient() {
+        let mut model = Mlp::new(
+            3,
+            &[(4, Activation::Tanh), (3, Activation::Sigmoid)],
+            Activation::Identity,
+            7,
+        );
+        assert_gradient(&mut model, &[1.0, -2.0, 0.5]);
+        let mut model = Mlp::new(2, &[(5, Activation::Relu)], Activation::Sigmoid, 7);
+        assert_gradient(&mut model, &vec![0.3, -0.7]);
+        let mut model = Mlp::new(2, &[], Activation::Tanh, 7);
+        assert_gradient(&mut model, &[0.3, -0.7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Layers of a perceptron must not be empty")]
+    fn mlp_empty_layer() {
+        Mlp::<[f64; 2]>::new(2, &[(0, Activation::Relu)], Activation::Identity, 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of inputs does not match the weights of the layer")]
+    fn mlp_wrong_input_dimension() {
+        let model = Mlp::new(3, &[(2, Activation::Relu)], Activation::Identity, 7);
+        model.predict(&vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn mlp_num_coefficients() {
+        let model: Mlp<[f64; 3]> = Mlp::new(3, &[(4, Activation::Relu)], Activation::Identity, 1);
+        assert_eq!(3 * 4 + 4 + 4 + 1, model.num_coefficients());
+    }
+
+    #[test]
+    fn logistic_gradient() {
+        assert_gradient(&mut Logistic::<[f64; 3]>::default(), &[1.0, -2.0, 0.5]);
+    }
+
+    #[test]
+    fn generalized_linear_model_gradient() {
+        let mut model = GeneralizedLinearModel::<[f64; 2], _>::new(|x| 1.0 / (1.0 + x.exp()));
+        assert_gradient(&mut model, &[0.4, 1.2]);
+        let mut model = GeneralizedLinearModel::<[f64; 2], _>::new(|x| x.powi(3) - x * 2.0);
+        assert_gradient(&mut model, &[0.4, 1.2]);
+    }
+
+    #[test]
+    fn one_vs_rest_gradient() {
+        let mut model = OneVsRest::<[Logistic<[f64; 2]>; 3]>::default();
+        assert_gradient(&mut model, &[0.4, 1.2]);
+    }
+
+    #[test]
+    fn pipeline_gradient() {
+        let transformer = StandardScaler::fit(2, [[1.0, 2.0], [3.0, -2.0]].iter());
+        let mut model = Pipeline {
+            transformer,
+            model: Logistic::<[f64; 2]>::default(),
+        };
+        assert_gradient(&mut model, &[0.4, 1.2]);
+        let mut model = Pipeline {
+            transformer: StandardScaler::fit(2, [[1.0, 2.0], [3.0, -2.0]].iter()),
+            model: Mlp::new(2, &[(3, Activation::Tanh)], Activation::Identity, 7),
+        };
+        assert_gradient(&mut model, &[0.4, 1.2]);
+    }
+
+    // y = a * tanh(b * x) + c
+    struct Tanh;
+
+    impl Function for Tanh {
+        type Features = f64;
+
+        fn predict<N: Number>(&self, coefficients: &[N], input: &f64) -> N {
+            coefficients[0] * (coefficients[1] * *input).tanh() + coefficients[2]
+        }
+    }
+
+    #[test]
+    fn auto_diff_gradient() {
+        assert_gradient(&mut AutoDiff::new(Tanh, 3), &0.8);
+    }
+
+    // Model with a deliberately wrong gradient
+    struct Square(f64);
+
+    impl Model for Square {
+        type Features = f64;
+        type Target = f64;
+
+        fn num_coefficients(&self) -> usize {
+            1
+        }
+
+        fn coefficient(&mut self, _: usize) -> &mut f64 {
+            &mut self.0
+        }
+
+        fn predict(&self, input: &f64) -> f64 {
+            (self.0 * input).powi(2)
+        }
+
+        fn gradient(&self, _: usize, input: &f64) -> f64 {
+            self.0 * input.powi(2) // should be 2 * a * x^2
+        }
+    }
+
+    #[test]
+    fn detects_wrong_gradient() {
+        let mut model = Square(0.5);
+        let mismatch = check_gradient(&mut model, &1.2, 1e-6);
+        assert!(mismatch[0] > 1e-3);
+    }
+}
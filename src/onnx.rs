@@ -0,0 +1,606 @@
+//! Exporting trained models to ONNX
+//!
+//! Supported models are turned into an affine transformation followed by an element-wise link
+//! function. The resulting graph consists of `MatMul`, `Add` and an optional activation node like
+//! `Sigmoid`. It takes a double tensor `features` of shape `[N, feature dimension]` and computes a
+//! double tensor `prediction` of shape `[N, number of outputs]`.
+//!
+//! # Example
+//!
+//! ```
+//! use vikos::model::Logistic;
+//! use vikos::onnx::Export;
+//! use vikos::Model;
+//!
+//! let mut model = Logistic::default();
+//! *model.coefficient(0) = 0.5;
+//!
+//! let graph = model.to_onnx().unwrap();
+//! assert_eq!(model.predict(&[1.0, 2.0]), graph.evaluate(&[1.0, 2.0])[0]);
+//!
+//! let mut file = Vec::new();
+//! graph.write(&mut file).unwrap();
+//! ```
+
+use crate::{
+    dual::Dual,
+    linear_algebra::Vector,
+    model::{GeneralizedLinearModel, Linear, Logistic, OneVsRest},
+};
+use std::{error, fmt, io};
+
+/// Element-wise function applied to the output of the affine transformation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// `y = x`. No node is emitted.
+    Identity,
+    /// `y = 1 / (1 + e^(-x))`
+    Sigmoid,
+    /// `y = e^x`
+    Exp,
+    /// `y = tanh(x)`
+    Tanh,
+}
+
+impl Link {
+    const ALL: [Link; 4] = [Link::Identity, Link::Sigmoid, Link::Exp, Link::Tanh];
+
+    /// Name of the ONNX operator, `None` for `Identity`
+    fn op_type(self) -> Option<&'static str> {
+        match self {
+            Link::Identity => None,
+            Link::Sigmoid => Some("Sigmoid"),
+            Link::Exp => Some("Exp"),
+            Link::Tanh => Some("Tanh"),
+        }
+    }
+
+    fn apply(self, x: f64) -> f64 {
+        match self {
+            Link::Identity => x,
+            Link::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Link::Exp => x.exp(),
+            Link::Tanh => x.tanh(),
+        }
+    }
+}
+
+/// Affine transformation followed by a link function, `y_j = link(w_j * x + b_j)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Affine {
+    /// Weights `w_j` for each output `j`
+    pub weights: Vec<Vec<f64>>,
+    /// Bias `b_j` for each output `j`
+    pub biases: Vec<f64>,
+    /// Applied to each output
+    pub link: Link,
+}
+
+impl Affine {
+    /// Builds the ONNX graph computing this transformation
+    pub fn graph(&self) -> Graph {
+        let outputs = self.biases.len();
+        let dimension = self.weights.first().map_or(0, Vec::len);
+        // ONNX expects the weights as a matrix of shape [dimension, outputs]
+        let weights = (0..dimension)
+            .flat_map(|i| self.weights.iter().map(move |w| w[i]))
+            .collect();
+
+        let mut nodes = vec![
+            Node::new("MatMul", &[INPUT, "weights"], "weighted"),
+            Node::new("Add", &["weighted", "biases"], "linear"),
+        ];
+        match self.link.op_type() {
+            Some(op_type) => nodes.push(Node::new(op_type, &["linear"], OUTPUT)),
+            None => nodes[1].outputs = vec![OUTPUT.to_string()],
+        }
+
+        Graph {
+            feature_dimension: dimension,
+            outputs,
+            nodes,
+            initializers: vec![
+                Tensor {
+                    name: "weights".to_string(),
+                    dims: vec![dimension, outputs],
+                    values: weights,
+                },
+                Tensor {
+                    name: "biases".to_string(),
+                    dims: vec![outputs],
+                    values: self.biases.clone(),
+                },
+            ],
+        }
+    }
+}
+
+/// Models which can be exported to ONNX
+pub trait Export {
+    /// Describes the prediction of the model as an affine transformation and a link function
+    fn affine(&self) -> Result<Affine, Error>;
+
+    /// Builds the ONNX graph computing the prediction of the model
+    fn to_onnx(&self) -> Result<Graph, Error> {
+        Ok(self.affine()?.graph())
+    }
+}
+
+fn weights<V: Vector>(linear: &Linear<V>, sign: f64) -> Vec<f64> {
+    (0..linear.m.dimension())
+        .map(|i| sign * linear.m.at(i))
+        .collect()
+}
+
+impl<V: Vector> Export for Linear<V> {
+    fn affine(&self) -> Result<Affine, Error> {
+        Ok(Affine {
+            weights: vec![weights(self, 1.0)],
+            biases: vec![self.c],
+            link: Link::Identity,
+        })
+    }
+}
+
+/// `Logistic` models `1 / (1 + e^(m * x + c))`, so the exported coefficients are negated
+impl<V: Vector> Export for Logistic<V> {
+    fn affine(&self) -> Result<Affine, Error> {
+        let linear = self.linear();
+        Ok(Affine {
+            weights: vec![weights(linear, -1.0)],
+            biases: vec![-linear.c],
+            link: Link::Sigmoid,
+        })
+    }
+}
+
+/// The link is recognized by evaluating `g` at some sample points. Links of the form `g(-x)` are
+/// supported by negating the coefficients.
+impl<V, G> Export for GeneralizedLinearModel<V, G>
+where
+    V: Vector,
+    G: Fn(Dual) -> Dual,
+{
+    fn affine(&self) -> Result<Affine, Error> {
+        let g = |x| (self.g)(Dual::constant(x)).value;
+        let samples = [-3.0, -1.0, -0.5, 0.0, 0.25, 1.0, 2.5];
+        for &sign in &[1.0, -1.0] {
+            for &link in &Link::ALL {
+                let matches = samples.iter().all(|&x| {
+                    let (expected, actual) = (link.apply(sign * x), g(x));
+                    (expected - actual).abs() <= 1e-9 * expected.abs().max(1.0)
+                });
+                if matches {
+                    return Ok(Affine {
+                        weights: vec![weights(&self.linear, sign)],
+                        biases: vec![sign * self.linear.c],
+                        link,
+                    });
+                }
+            }
+        }
+        Err(Error::UnknownLink)
+    }
+}
+
+/// All binary classifiers must share the same link function
+impl<M: Export, const N: usize> Export for OneVsRest<[M; N]> {
+    fn affine(&self) -> Result<Affine, Error> {
+        let mut result = Affine {
+            weights: Vec::new(),
+            biases: Vec::new(),
+            link: Link::Identity,
+        };
+        for (class, model) in self.models().iter().enumerate() {
+            let affine = model.affine()?;
+            if class == 0 {
+                result.link = affine.link;
+            } else if affine.link != result.link {
+                return Err(Error::MixedLinks);
+            }
+            result.weights.extend(affine.weights);
+            result.biases.extend(affine.biases);
+        }
+        Ok(result)
+    }
+}
+
+/// Errors occurring while exporting a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The outer function of a `GeneralizedLinearModel` is none of the supported links
+    UnknownLink,
+    /// The binary classifiers of a `OneVsRest` model use different links
+    MixedLinks,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownLink => write!(f, "link function can not be expressed in ONNX"),
+            Error::MixedLinks => write!(f, "binary classifiers use different link functions"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Name of the input of the graph
+const INPUT: &str = "features";
+/// Name of the output of the graph
+const OUTPUT: &str = "prediction";
+
+/// Operator in an ONNX graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// Name of the ONNX operator, e.g. `MatMul`
+    pub op_type: String,
+    /// Names of the input values
+    pub inputs: Vec<String>,
+    /// Names of the output values
+    pub outputs: Vec<String>,
+}
+
+impl Node {
+    fn new(op_type: &str, inputs: &[&str], output: &str) -> Node {
+        Node {
+            op_type: op_type.to_string(),
+            inputs: inputs.iter().map(|&input| input.to_string()).collect(),
+            outputs: vec![output.to_string()],
+        }
+    }
+}
+
+/// Constant tensor of doubles stored in an ONNX graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    /// Name by which nodes refer to the tensor
+    pub name: String,
+    /// Shape of the tensor
+    pub dims: Vec<usize>,
+    /// Elements in row major order
+    pub values: Vec<f64>,
+}
+
+/// ONNX graph computing the prediction of a model
+///
+/// Nodes are stored in topological order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    /// Number of columns of the input `features`
+    pub feature_dimension: usize,
+    /// Number of columns of the output `prediction`
+    pub outputs: usize,
+    /// Operators of the graph
+    pub nodes: Vec<Node>,
+    /// Constants like weights and biases
+    pub initializers: Vec<Tensor>,
+}
+
+/// ONNX element type for doubles
+const DOUBLE: u64 = 11;
+/// Version of the ONNX intermediate representation written
+const IR_VERSION: u64 = 7;
+/// Version of the default ONNX operator set used
+const OPSET_VERSION: u64 = 13;
+
+impl Graph {
+    /// Evaluates the graph for the features of a single event
+    ///
+    /// Useful to check the exported graph against `Model::predict`. Only supports the operators
+    /// emitted by this module.
+    pub fn evaluate(&self, features: &[f64]) -> Vec<f64> {
+        assert_eq!(self.feature_dimension, features.len());
+        // Each value is a row vector, or a matrix stored in row major order
+        type Value = (Vec<usize>, Vec<f64>);
+        let mut values: Vec<(String, Value)> = vec![(
+            INPUT.to_string(),
+            (vec![1, features.len()], features.to_vec()),
+        )];
+        for tensor in &self.initializers {
+            let dims = match tensor.dims.len() {
+                1 => vec![1, tensor.dims[0]],
+                _ => tensor.dims.clone(),
+            };
+            values.push((tensor.name.clone(), (dims, tensor.values.clone())));
+        }
+        let get = |values: &[(String, Value)], name: &str| {
+            values
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_else(|| panic!("Value '{}' not defined", name))
+        };
+        for node in &self.nodes {
+            let (dims, a) = get(&values, &node.inputs[0]);
+            let result = match node.op_type.as_str() {
+                "MatMul" => {
+                    let (b_dims, b) = get(&values, &node.inputs[1]);
+                    assert_eq!(dims[1], b_dims[0]);
+                    let columns = b_dims[1];
+                    let product = (0..columns)
+                        .map(|j| (0..dims[1]).map(|k| a[k] * b[k * columns + j]).sum())
+                        .collect();
+                    (vec![1, columns], product)
+                }
+                "Add" => {
+                    let (_, b) = get(&values, &node.inputs[1]);
+                    (dims, a.iter().zip(b).map(|(a, b)| a + b).collect())
+                }
+                op_type => {
+                    let link = Link::ALL
+                        .iter()
+                        .find(|link| link.op_type() == Some(op_type))
+                        .unwrap_or_else(|| panic!("Operator '{}' not supported", op_type));
+                    (dims, a.iter().map(|&x| link.apply(x)).collect())
+                }
+            };
+            values.push((node.outputs[0].clone(), result));
+        }
+        get(&values, OUTPUT).1
+    }
+
+    /// Encodes the graph as an ONNX `ModelProto`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut model = Encoder::default();
+        model.varint(1, IR_VERSION);
+        model.string(2, "vikos");
+        model.string(3, env!("CARGO_PKG_VERSION"));
+        model.message(7, &self.encode_graph());
+        let mut opset = Encoder::default();
+        opset.string(1, "");
+        opset.varint(2, OPSET_VERSION);
+        model.message(8, &opset);
+        model.bytes
+    }
+
+    /// Writes the graph as an ONNX model file to `writer`
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    fn encode_graph(&self) -> Encoder {
+        let mut graph = Encoder::default();
+        for node in &self.nodes {
+            let mut encoded = Encoder::default();
+            for input in &node.inputs {
+                encoded.string(1, input);
+            }
+            for output in &node.outputs {
+                encoded.string(2, output);
+            }
+            encoded.string(4, &node.op_type);
+            graph.message(1, &encoded);
+        }
+        graph.string(2, "vikos");
+        for tensor in &self.initializers {
+            let mut encoded = Encoder::default();
+            for &dim in &tensor.dims {
+                encoded.varint(1, dim as u64);
+            }
+            encoded.varint(2, DOUBLE);
+            encoded.string(8, &tensor.name);
+            let raw: Vec<u8> = tensor
+                .values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            encoded.length_delimited(9, &raw);
+            graph.message(5, &encoded);
+        }
+        graph.message(11, &value_info(INPUT, self.feature_dimension));
+        graph.message(12, &value_info(OUTPUT, self.outputs));
+        graph
+    }
+}
+
+/// Describes a double matrix with a variable number of rows
+fn value_info(name: &str, columns: usize) -> Encoder {
+    let mut shape = Encoder::default();
+    let mut rows = Encoder::default();
+    rows.string(2, "N");
+    shape.message(1, &rows);
+    let mut dimension = Encoder::default();
+    dimension.varint(1, columns as u64);
+    shape.message(1, &dimension);
+
+    let mut tensor_type = Encoder::default();
+    tensor_type.varint(1, DOUBLE);
+    tensor_type.message(2, &shape);
+    let mut type_proto = Encoder::default();
+    type_proto.message(1, &tensor_type);
+
+    let mut info = Encoder::default();
+    info.string(1, name);
+    info.message(2, &type_proto);
+    info
+}
+
+/// Writes fields in the protocol buffers wire format
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u64, value: u64) {
+        self.raw_varint(field << 3);
+        self.raw_varint(value);
+    }
+
+    fn length_delimited(&mut self, field: u64, value: &[u8]) {
+        self.raw_varint(field << 3 | 2);
+        self.raw_varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    fn string(&mut self, field: u64, value: &str) {
+        self.length_delimited(field, value.as_bytes())
+    }
+
+    fn message(&mut self, field: u64, value: &Encoder) {
+        self.length_delimited(field, &value.bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Encoder, Error, Export, Link};
+    use crate::model::{GeneralizedLinearModel, Linear, Logistic, OneVsRest};
+    use crate::{dual::Number, linear_algebra::Vector, Model};
+
+    /// Compares the prediction of the exported graph with the prediction of `model`
+    fn assert_same_prediction<M>(model: &M, inputs: &[[f64; 2]])
+    where
+        M: Model<Features = [f64; 2]> + Export,
+        M::Target: Vector,
+    {
+        let graph = model.to_onnx().unwrap();
+        for input in inputs {
+            let expected = model.predict(input);
+            let actual = graph.evaluate(input);
+            assert_eq!(expected.dimension(), actual.len());
+            for (i, a) in actual.into_iter().enumerate() {
+                let e = expected.at(i);
+                assert!((e - a).abs() < 1e-12, "expected {}, got {}", e, a);
+            }
+        }
+    }
+
+    const INPUTS: [[f64; 2]; 3] = [[0.0, 0.0], [1.5, -2.0], [-0.3, 4.0]];
+
+    #[test]
+    fn linear() {
+        let model = Linear {
+            m: [0.5, -1.5],
+            c: 2.0,
+        };
+        assert_same_prediction(&model, &INPUTS);
+        let graph = model.to_onnx().unwrap();
+        let ops: Vec<_> = graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(vec!["MatMul", "Add"], ops);
+        assert_eq!(vec!["prediction"], graph.nodes[1].outputs);
+    }
+
+    #[test]
+    fn logistic() {
+        let mut model = Logistic::default();
+        *model.coefficient(0) = 0.7;
+        *model.coefficient(1) = -0.2;
+        *model.coefficient(2) = 0.4;
+        assert_same_prediction(&model, &INPUTS);
+    }
+
+    #[test]
+    fn one_vs_rest() {
+        let mut model = OneVsRest::<[Logistic<[f64; 2]>; 3]>::default();
+        for i in 0..model.num_coefficients() {
+            *model.coefficient(i) = 0.1 * i as f64 - 0.3;
+        }
+        assert_same_prediction(&model, &INPUTS);
+        assert_eq!(3, model.to_onnx().unwrap().outputs);
+    }
+
+    #[test]
+    fn generalized_linear_model() {
+        let mut model = GeneralizedLinearModel::new(|x: crate::dual::Dual| x.exp());
+        model.linear = Linear {
+            m: [0.3, 0.1],
+            c: -0.5,
+        };
+        assert_same_prediction(&model, &INPUTS);
+        assert_eq!(Link::Exp, model.affine().unwrap().link);
+
+        let logistic = GeneralizedLinearModel {
+            linear: model.linear.clone(),
+            g: |x: crate::dual::Dual| 1.0 / (1.0 + x.exp()),
+        };
+        assert_same_prediction(&logistic, &INPUTS);
+
+        let square = GeneralizedLinearModel {
+            linear: model.linear.clone(),
+            g: |x: crate::dual::Dual| x * x,
+        };
+        assert_eq!(Err(Error::UnknownLink), square.to_onnx());
+    }
+
+    #[test]
+    fn varint() {
+        let mut encoder = Encoder::default();
+        encoder.varint(1, 300);
+        assert_eq!(vec![0x08, 0xac, 0x02], encoder.bytes);
+    }
+
+    /// Splits a protocol buffer message into its fields. Returns field number, wire type and
+    /// either the value of a varint or the content of a length delimited field.
+    fn fields(mut bytes: &[u8]) -> Vec<(u64, u64, u64, &[u8])> {
+        fn varint(bytes: &mut &[u8]) -> u64 {
+            let mut value = 0;
+            let mut shift = 0;
+            loop {
+                let byte = bytes[0];
+                *bytes = &bytes[1..];
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte < 0x80 {
+                    return value;
+                }
+                shift += 7;
+            }
+        }
+        let mut result = Vec::new();
+        while !bytes.is_empty() {
+            let key = varint(&mut bytes);
+            let (field, wire_type) = (key >> 3, key & 7);
+            match wire_type {
+                0 => result.push((field, wire_type, varint(&mut bytes), &[][..])),
+                2 => {
+                    let length = varint(&mut bytes) as usize;
+                    result.push((field, wire_type, 0, &bytes[..length]));
+                    bytes = &bytes[length..];
+                }
+                _ => panic!("Unexpected wire type {}", wire_type),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn wire_format() {
+        let model = Linear {
+            m: [0.5, -1.5],
+            c: 2.0,
+        };
+        let bytes = model.to_onnx().unwrap().to_bytes();
+        let model_fields = fields(&bytes);
+        let field_numbers: Vec<_> = model_fields.iter().map(|f| f.0).collect();
+        assert_eq!(vec![1, 2, 3, 7, 8], field_numbers);
+        assert_eq!(7, model_fields[0].2);
+
+        let graph = fields(model_fields[3].3);
+        let nodes: Vec<_> = graph.iter().filter(|f| f.0 == 1).collect();
+        assert_eq!(2, nodes.len());
+        let matmul = fields(nodes[0].3);
+        assert_eq!((4, &b"MatMul"[..]), (matmul[3].0, matmul[3].3));
+
+        let weights = fields(graph.iter().find(|f| f.0 == 5).unwrap().3);
+        let raw = weights.iter().find(|f| f.0 == 9).unwrap().3;
+        let values: Vec<f64> = raw
+            .chunks(8)
+            .map(|chunk| {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(chunk);
+                f64::from_le_bytes(bytes)
+            })
+            .collect();
+        assert_eq!(vec![0.5, -1.5], values);
+    }
+}
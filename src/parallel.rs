@@ -0,0 +1,269 @@
+//! Training a model on several threads
+//!
+//! The history is split into disjoint shards, one for each thread. Each thread trains a copy of
+//! the model on its shard, using its own `Teacher::Training`. How the copies are combined is
+//! determined by `Mode`. Requires the `rayon` cargo feature.
+
+use crate::{merge, Cost, Model, Teacher};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How threads combine their updates of the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Lock free updates of shared coefficients
+    ///
+    /// Before each event a thread reads the shared coefficients, after it adds its change to them.
+    /// Updates of different threads may interleave, so results vary between runs.
+    Hogwild,
+    /// Each thread trains its copy for `interval` events, then all copies are replaced by their
+    /// average
+    ///
+    /// Shards which have run out of events are left out of the average.
+    ///
+    /// This mode is deterministic. The result depends only on the history, the number of shards
+    /// and the interval, not on the scheduling of the threads.
+    Averaging {
+        /// Number of events each thread learns between two averaging steps
+        interval: usize,
+    },
+}
+
+/// Teaches `model` all events in `history`, splitting the work across `shards` threads
+///
+/// The history is split into `shards` contiguous parts of (almost) equal size. Sorted histories
+/// should be shuffled first, see `dataset::epochs`. Threads are taken from the global `rayon`
+/// thread pool.
+///
+/// # Example
+///
+/// ```
+/// use vikos::parallel::{learn_history_parallel, Mode};
+/// use vikos::{cost, model, teacher};
+///
+/// let history: Vec<_> = (0..1000)
+///     .map(|i| {
+///         let x = (i % 10) as f64;
+///         ([x], 2.0 * x + 1.0)
+///     })
+///     .collect();
+/// let mut model = model::Linear::<[f64; 1]>::default();
+/// let teacher = teacher::GradientDescent { learning_rate: 0.01 };
+/// let cost = cost::LeastSquares {};
+///
+/// let mode = Mode::Averaging { interval: 50 };
+///
+/// learn_history_parallel(&teacher, &cost, &mut model, &history, 4, mode);
+/// assert!((model.m[0] - 2.0).abs() < 0.1);
+/// ```
+pub fn learn_history_parallel<M, C, T, Truth>(
+    teacher: &T,
+    cost: &C,
+    model: &mut M,
+    history: &[(M::Features, Truth)],
+    shards: usize,
+    mode: Mode,
+) where
+    M: Model + Clone + Send + Sync,
+    M::Features: Sync,
+    C: Cost<Truth, M::Target> + Sync,
+    T: Teacher<M> + Sync,
+    T::Training: Send,
+    Truth: Copy + Sync,
+{
+    assert!(shards > 0, "Number of shards must not be zero");
+    let shards: Vec<_> = (0..shards)
+        .map(|i| &history[i * history.len() / shards..(i + 1) * history.len() / shards])
+        .collect();
+    match mode {
+        Mode::Hogwild => hogwild(teacher, cost, model, &shards),
+        Mode::Averaging { interval } => averaging(teacher, cost, model, &shards, interval),
+    }
+}
+
+fn coefficients<M: Model>(model: &mut M) -> Vec<f64> {
+    (0..model.num_coefficients())
+        .map(|ci| *model.coefficient(ci))
+        .collect()
+}
+
+fn hogwild<M, C, T, Truth>(teacher: &T, cost: &C, model: &mut M, shards: &[&[(M::Features, Truth)]])
+where
+    M: Model + Clone + Send + Sync,
+    M::Features: Sync,
+    C: Cost<Truth, M::Target> + Sync,
+    T: Teacher<M> + Sync,
+    T::Training: Send,
+    Truth: Copy + Sync,
+{
+    // Coefficients are stored as the bits of an f64
+    let shared: Vec<AtomicU64> = coefficients(model)
+        .into_iter()
+        .map(|c| AtomicU64::new(c.to_bits()))
+        .collect();
+    let prototype: &M = model;
+
+    shards.par_iter().for_each(|shard| {
+        let mut local = prototype.clone();
+        let mut training = teacher.new_training(&local);
+        let mut before = vec![0.0; shared.len()];
+        for (features, truth) in shard.iter() {
+            for (ci, (coefficient, before)) in shared.iter().zip(&mut before).enumerate() {
+                *before = f64::from_bits(coefficient.load(Ordering::Relaxed));
+                *local.coefficient(ci) = *before;
+            }
+            teacher.teach_event(&mut training, &mut local, cost, features, *truth);
+            for (ci, (coefficient, before)) in shared.iter().zip(&before).enumerate() {
+                let delta = *local.coefficient(ci) - before;
+                if delta != 0.0 {
+                    // Never fails, since the closure always returns `Some`
+                    let _ = coefficient.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| {
+                        Some((f64::from_bits(c) + delta).to_bits())
+                    });
+                }
+            }
+        }
+    });
+
+    for (ci, coefficient) in shared.into_iter().enumerate() {
+        *model.coefficient(ci) = f64::from_bits(coefficient.into_inner());
+    }
+}
+
+fn averaging<M, C, T, Truth>(
+    teacher: &T,
+    cost: &C,
+    model: &mut M,
+    shards: &[&[(M::Features, Truth)]],
+    interval: usize,
+) where
+    M: Model + Clone + Send + Sync,
+    M::Features: Sync,
+    C: Cost<Truth, M::Target> + Sync,
+    T: Teacher<M> + Sync,
+    T::Training: Send,
+    Truth: Copy + Sync,
+{
+    assert!(interval > 0, "Averaging interval must not be zero");
+    let mut workers: Vec<_> = shards
+        .iter()
+        .map(|&shard| (model.clone(), teacher.new_training(model), shard))
+        .collect();
+    let longest = shards.iter().map(|shard| shard.len()).max().unwrap_or(0);
+
+    for start in (0..longest).step_by(interval) {
+        workers.par_iter_mut().for_each(|(local, training, shard)| {
+            for (features, truth) in shard.iter().skip(start).take(interval) {
+                teacher.teach_event(training, local, cost, features, *truth);
+            }
+        });
+
+        // Average in a fixed order, so the result does not depend on the scheduling. Copies of
+        // the same model always have the same number of coefficients. Copies of exhausted shards
+        // have not learned anything new and are left out, so they do not hold back the others.
+        // The longest shard is never exhausted here, so at least one copy is averaged.
+        let active = workers
+            .iter()
+            .filter(|(_, _, shard)| start < shard.len())
+            .map(|(local, _, _)| local.clone());
+        *model = merge::average(active).expect("Copies of a model must have the same shape");
+        for (local, _, _) in &mut workers {
+            local.clone_from(model);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{learn_history_parallel, Mode};
+    use crate::{cost::LeastSquares, model::Linear, teacher::GradientDescent};
+
+    fn history() -> Vec<([f64; 2], f64)> {
+        (0..2000)
+            .map(|i| {
+                let x = [(i % 7) as f64 - 3.0, (i % 5) as f64 - 2.0];
+                (x, 1.5 * x[0] - 0.5 * x[1] + 2.0)
+            })
+            .collect()
+    }
+
+    fn train(mode: Mode, shards: usize) -> Linear<[f64; 2]> {
+        let mut model = Linear::default();
+        let teacher = GradientDescent {
+            learning_rate: 0.01,
+        };
+        learn_history_parallel(
+            &teacher,
+            &LeastSquares {},
+            &mut model,
+            &history(),
+            shards,
+            mode,
+        );
+        model
+    }
+
+    fn assert_converged(model: &Linear<[f64; 2]>) {
+        println!("{:?}", model);
+        assert!((model.m[0] - 1.5).abs() < 0.01);
+        assert!((model.m[1] + 0.5).abs() < 0.01);
+        assert!((model.c - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn hogwild() {
+        assert_converged(&train(Mode::Hogwild, 4));
+    }
+
+    #[test]
+    fn averaging_is_deterministic() {
+        let mode = Mode::Averaging { interval: 25 };
+        let model = train(mode, 4);
+        assert_converged(&model);
+        for _ in 0..3 {
+            let other = train(mode, 4);
+            assert_eq!(model.m, other.m);
+            assert_eq!(model.c, other.c);
+        }
+    }
+
+    #[test]
+    fn averaging_skips_exhausted_shards() {
+        // Three events split into shards of one and two events
+        let history = [([1.0], 2.0), ([2.0], 3.0), ([3.0], 1.0)];
+        let teacher = GradientDescent { learning_rate: 0.1 };
+        let cost = LeastSquares {};
+
+        let mut parallel = Linear::<[f64; 1]>::default();
+        let mode = Mode::Averaging { interval: 1 };
+        learn_history_parallel(&teacher, &cost, &mut parallel, &history, 2, mode);
+
+        // After the first interval both copies are averaged, the second one only concerns the
+        // longer shard
+        let mut first = Linear::<[f64; 1]>::default();
+        let mut second = first.clone();
+        crate::learn_history(&teacher, &cost, &mut first, history[..1].iter().cloned());
+        crate::learn_history(&teacher, &cost, &mut second, history[1..2].iter().cloned());
+        let mut expected = crate::merge::average(vec![first, second]).unwrap();
+        crate::learn_history(&teacher, &cost, &mut expected, history[2..].iter().cloned());
+
+        assert_eq!(expected.m, parallel.m);
+        assert_eq!(expected.c, parallel.c);
+    }
+
+    #[test]
+    fn single_shard_equals_sequential() {
+        let mut sequential = Linear::default();
+        let teacher = GradientDescent {
+            learning_rate: 0.01,
+        };
+        crate::learn_history(&teacher, &LeastSquares {}, &mut sequential, history());
+
+        for &mode in &[Mode::Hogwild, Mode::Averaging { interval: 7 }] {
+            let parallel = train(mode, 1);
+            assert_eq!(sequential.m, parallel.m);
+            assert_eq!(sequential.c, parallel.c);
+        }
+    }
+}
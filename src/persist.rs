@@ -0,0 +1,485 @@
+//! Storing trained models in files
+//!
+//! Models are wrapped in a versioned envelope, stating the format version, the kind of the model,
+//! its feature dimension and metadata about its training. Loading rejects files which do not match
+//! the expected model with a descriptive error. Saving and loading JSON requires the `json`
+//! cargo feature, the compact binary encoding requires the `bincode` feature.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "json")]
+//! # {
+//! use vikos::model::Linear;
+//! use vikos::persist::{load_json, save_json, Metadata};
+//!
+//! let model = Linear { m: [1.0, 2.0], c: 3.0 };
+//! let metadata = Metadata {
+//!     events: Some(1000),
+//!     ..Metadata::default()
+//! };
+//! let mut file = Vec::new();
+//! save_json(&mut file, &model, &metadata).unwrap();
+//!
+//! let (loaded, metadata): (Linear<[f64; 2]>, _) = load_json(&file[..]).unwrap();
+//! assert_eq!(model.m, loaded.m);
+//! assert_eq!(Some(1000), metadata.events);
+//! # }
+//! ```
+
+use crate::{
+    array::Array,
+    linear_algebra::Vector,
+    model::{Linear, Logistic, OneVsRest},
+    Model,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error, fmt, io};
+
+/// Version of the file format written by this version of vikos
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Identifies files written by vikos
+#[cfg(any(feature = "json", feature = "bincode"))]
+const FORMAT: &str = "vikos";
+
+/// Models which can be saved to and loaded from files
+pub trait Persistent: Model + Serialize + DeserializeOwned {
+    /// Identifies the kind of model in stored files, e.g. `Linear`
+    fn kind() -> String;
+
+    /// Dimension of the features the model expects
+    fn feature_dimension(&self) -> usize;
+}
+
+impl<V> Persistent for Linear<V>
+where
+    V: Vector + Serialize + DeserializeOwned,
+{
+    fn kind() -> String {
+        "Linear".to_string()
+    }
+
+    fn feature_dimension(&self) -> usize {
+        self.m.dimension()
+    }
+}
+
+impl<V> Persistent for Logistic<V>
+where
+    V: Vector + Serialize + DeserializeOwned,
+{
+    fn kind() -> String {
+        "Logistic".to_string()
+    }
+
+    fn feature_dimension(&self) -> usize {
+        self.num_coefficients() - 1
+    }
+}
+
+impl<M, const N: usize> Persistent for OneVsRest<[M; N]>
+where
+    M: Persistent<Target = f64>,
+    [M; N]: Array<Element = M> + Serialize + DeserializeOwned,
+{
+    fn kind() -> String {
+        format!("OneVsRest<{}; {}>", M::kind(), N)
+    }
+
+    fn feature_dimension(&self) -> usize {
+        self.models().at_ref(0).feature_dimension()
+    }
+}
+
+/// Information about the training of a model, stored alongside it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Version of vikos which saved the model. Set while saving.
+    pub vikos_version: String,
+    /// Number of events the model has been trained with
+    pub events: Option<u64>,
+    /// Free form properties, e.g. the teacher and cost function used during training
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Describes the stored model. Written in front of the model itself.
+#[cfg(any(feature = "json", feature = "bincode"))]
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    kind: String,
+    feature_dimension: usize,
+    metadata: Metadata,
+}
+
+#[cfg(any(feature = "json", feature = "bincode"))]
+impl Header {
+    fn new<M: Persistent>(model: &M, metadata: &Metadata) -> Header {
+        Header {
+            format: FORMAT.to_string(),
+            version: FORMAT_VERSION,
+            kind: M::kind(),
+            feature_dimension: model.feature_dimension(),
+            metadata: Metadata {
+                vikos_version: env!("CARGO_PKG_VERSION").to_string(),
+                ..metadata.clone()
+            },
+        }
+    }
+
+    /// Checks whether a model of type `M` can be read after this header
+    fn check<M: Persistent>(&self) -> Result<(), Error> {
+        if self.format != FORMAT {
+            return Err(Error::NotAModel);
+        }
+        check_version(self.version)?;
+        if self.kind != M::kind() {
+            return Err(Error::KindMismatch {
+                expected: M::kind(),
+                found: self.kind.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the loaded `model` against the header and returns it together with its metadata
+    fn accept<M: Persistent>(self, model: M) -> Result<(M, Metadata), Error> {
+        if model.feature_dimension() != self.feature_dimension {
+            return Err(Error::DimensionMismatch {
+                header: self.feature_dimension,
+                model: model.feature_dimension(),
+            });
+        }
+        Ok((model, self.metadata))
+    }
+}
+
+#[cfg(any(feature = "json", feature = "bincode"))]
+fn check_version(version: u32) -> Result<(), Error> {
+    if version == 0 || version > FORMAT_VERSION {
+        Err(Error::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Errors occurring while saving or loading a model
+///
+/// Variants may be added in the future, e.g. for new formats or optional features.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Error reading or writing the underlying file
+    Io(io::Error),
+    /// Error encoding or decoding JSON
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// Error encoding or decoding the binary format
+    #[cfg(feature = "bincode")]
+    Bincode(bincode::Error),
+    /// The file has not been written by vikos
+    NotAModel,
+    /// The file has been written in a format version this version of vikos can not read
+    UnsupportedVersion {
+        /// Version of the file
+        found: u32,
+        /// Latest version supported
+        supported: u32,
+    },
+    /// The file contains a different kind of model than requested
+    KindMismatch {
+        /// Kind of the requested model
+        expected: String,
+        /// Kind of the model in the file
+        found: String,
+    },
+    /// The feature dimension of the stored model does not match the one stated in the header
+    DimensionMismatch {
+        /// Feature dimension stated in the header
+        header: usize,
+        /// Feature dimension of the stored model
+        model: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            #[cfg(feature = "json")]
+            Error::Json(error) => write!(f, "{}", error),
+            #[cfg(feature = "bincode")]
+            Error::Bincode(error) => write!(f, "{}", error),
+            Error::NotAModel => write!(f, "not a vikos model file"),
+            Error::UnsupportedVersion { found, supported } => write!(
+                f,
+                "format version {} is not supported, latest supported version is {}",
+                found, supported
+            ),
+            Error::KindMismatch { expected, found } => write!(
+                f,
+                "file contains a model of kind '{}', expected '{}'",
+                found, expected
+            ),
+            Error::DimensionMismatch { header, model } => write!(
+                f,
+                "header states feature dimension {}, but the model has {}",
+                header, model
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl From<bincode::Error> for Error {
+    fn from(error: bincode::Error) -> Error {
+        Error::Bincode(error)
+    }
+}
+
+/// Writes `model` as a JSON document to `writer`
+#[cfg(feature = "json")]
+pub fn save_json<M, W>(writer: W, model: &M, metadata: &Metadata) -> Result<(), Error>
+where
+    M: Persistent,
+    W: io::Write,
+{
+    #[derive(Serialize)]
+    struct Document<'a, M> {
+        header: Header,
+        model: &'a M,
+    }
+
+    let document = Document {
+        header: Header::new(model, metadata),
+        model,
+    };
+    serde_json::to_writer_pretty(writer, &document)?;
+    Ok(())
+}
+
+/// Reads a model written by `save_json` from `reader`
+#[cfg(feature = "json")]
+pub fn load_json<M, R>(reader: R) -> Result<(M, Metadata), Error>
+where
+    M: Persistent,
+    R: io::Read,
+{
+    use serde_json::Value;
+
+    let mut document: Value = serde_json::from_reader(reader)?;
+    // Check format and version first, later versions may change the layout of the header
+    if document["header"]["format"] != FORMAT {
+        return Err(Error::NotAModel);
+    }
+    match document["header"]["version"].as_u64() {
+        Some(version) => check_version(version.min(u64::from(u32::MAX)) as u32)?,
+        None => return Err(Error::NotAModel),
+    }
+    let header: Header = serde_json::from_value(document["header"].take())?;
+    header.check::<M>()?;
+    let model = serde_json::from_value(document["model"].take())?;
+    header.accept(model)
+}
+
+/// Identifies binary files written by vikos
+#[cfg(feature = "bincode")]
+const MAGIC: &[u8] = b"VIKOS";
+
+/// Writes `model` in a compact binary encoding to `writer`
+#[cfg(feature = "bincode")]
+pub fn save_bincode<M, W>(mut writer: W, model: &M, metadata: &Metadata) -> Result<(), Error>
+where
+    M: Persistent,
+    W: io::Write,
+{
+    let header = Header::new(model, metadata);
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut writer, &header.version)?;
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(&mut writer, model)?;
+    Ok(())
+}
+
+/// Reads a model written by `save_bincode` from `reader`
+#[cfg(feature = "bincode")]
+pub fn load_bincode<M, R>(mut reader: R) -> Result<(M, Metadata), Error>
+where
+    M: Persistent,
+    R: io::Read,
+{
+    let mut magic = [0; 5];
+    match reader.read_exact(&mut magic) {
+        Ok(()) if magic == MAGIC => (),
+        Ok(()) => return Err(Error::NotAModel),
+        Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(Error::NotAModel)
+        }
+        Err(error) => return Err(error.into()),
+    }
+    // Check version first, later versions may change the layout of the header
+    check_version(bincode::deserialize_from(&mut reader)?)?;
+    let header: Header = bincode::deserialize_from(&mut reader)?;
+    header.check::<M>()?;
+    let model = bincode::deserialize_from(&mut reader)?;
+    header.accept(model)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Error, Persistent};
+    use crate::model::{Linear, Logistic, OneVsRest};
+
+    #[cfg(any(feature = "json", feature = "bincode"))]
+    fn metadata() -> super::Metadata {
+        let mut metadata = super::Metadata {
+            events: Some(150),
+            ..Default::default()
+        };
+        metadata
+            .properties
+            .insert("teacher".to_string(), "GradientDescent".to_string());
+        metadata
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!("Linear", Linear::<Vec<f64>>::kind());
+        assert_eq!(
+            "OneVsRest<Logistic; 3>",
+            OneVsRest::<[Logistic<[f64; 4]>; 3]>::kind()
+        );
+        let model = OneVsRest::new([Logistic::<Vec<f64>>::with_feature_dimension(5)]);
+        assert_eq!(5, model.feature_dimension());
+    }
+
+    #[cfg(feature = "json")]
+    mod json {
+
+        use super::super::{load_json, save_json, Error};
+        use super::metadata;
+        use crate::model::{Linear, Logistic, OneVsRest};
+
+        #[test]
+        fn round_trip() {
+            let model = OneVsRest::new([
+                Logistic::<Vec<f64>>::with_feature_dimension(2),
+                Logistic::with_feature_dimension(2),
+            ]);
+            let mut file = Vec::new();
+            save_json(&mut file, &model, &metadata()).unwrap();
+
+            let (loaded, loaded_metadata): (OneVsRest<[Logistic<Vec<f64>>; 2]>, _) =
+                load_json(&file[..]).unwrap();
+            assert_eq!(format!("{:?}", model), format!("{:?}", loaded));
+            assert_eq!(metadata().properties, loaded_metadata.properties);
+            assert_eq!(env!("CARGO_PKG_VERSION"), loaded_metadata.vikos_version);
+        }
+
+        #[test]
+        fn reject_incompatible() {
+            let mut file = Vec::new();
+            save_json(&mut file, &Linear { m: [1.0], c: 2.0 }, &metadata()).unwrap();
+            match load_json::<Logistic<[f64; 1]>, _>(&file[..]) {
+                Err(Error::KindMismatch { expected, found }) => {
+                    assert_eq!("Logistic", expected);
+                    assert_eq!("Linear", found);
+                }
+                other => panic!("expected kind mismatch, got {:?}", other),
+            }
+
+            let future = String::from_utf8(file)
+                .unwrap()
+                .replace("\"version\": 1", "\"version\": 2");
+            assert_eq!(
+                "format version 2 is not supported, latest supported version is 1",
+                load_json::<Linear<[f64; 1]>, _>(future.as_bytes())
+                    .unwrap_err()
+                    .to_string()
+            );
+
+            match load_json::<Linear<[f64; 1]>, _>(&b"{\"m\": [1.0], \"c\": 2.0}"[..]) {
+                Err(Error::NotAModel) => (),
+                other => panic!("expected not a model, got {:?}", other),
+            }
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    mod bincode {
+
+        use super::super::{load_bincode, save_bincode, Error};
+        use super::metadata;
+        use crate::model::{Linear, Logistic};
+
+        #[test]
+        fn round_trip() {
+            let model = Linear {
+                m: vec![1.0, -2.0, 0.5],
+                c: 3.0,
+            };
+            let mut file = Vec::new();
+            save_bincode(&mut file, &model, &metadata()).unwrap();
+
+            let (loaded, loaded_metadata): (Linear<Vec<f64>>, _) = load_bincode(&file[..]).unwrap();
+            assert_eq!(model.m, loaded.m);
+            assert_eq!(model.c, loaded.c);
+            assert_eq!(Some(150), loaded_metadata.events);
+        }
+
+        #[test]
+        fn reject_incompatible() {
+            let mut file = Vec::new();
+            save_bincode(&mut file, &Linear { m: [1.0], c: 2.0 }, &metadata()).unwrap();
+            assert!(matches!(
+                load_bincode::<Logistic<[f64; 1]>, _>(&file[..]),
+                Err(Error::KindMismatch { .. })
+            ));
+            assert!(matches!(
+                load_bincode::<Linear<[f64; 1]>, _>(&b"VIK"[..]),
+                Err(Error::NotAModel)
+            ));
+
+            // Version directly follows the magic bytes
+            file[5] = 2;
+            assert!(matches!(
+                load_bincode::<Linear<[f64; 1]>, _>(&file[..]),
+                Err(Error::UnsupportedVersion { found: 2, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        let error = Error::DimensionMismatch {
+            header: 3,
+            model: 2,
+        };
+        assert_eq!(
+            "header states feature dimension 3, but the model has 2",
+            error.to_string()
+        );
+    }
+}
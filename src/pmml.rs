@@ -0,0 +1,849 @@
+//! Exporting and importing models as PMML
+//!
+//! `Linear`, `Logistic` and `OneVsRest<[Logistic; N]>` are written as PMML 4.4 `RegressionModel`
+//! documents. Names of the feature and target fields are supplied by the user. `Logistic` is
+//! exported as a regression with `normalizationMethod="logit"`, `OneVsRest` as a classification
+//! with one `RegressionTable` per class, each normalized with `logit`. Since `Logistic` models
+//! `1 / (1 + e^(m * x + c))` and PMML's `logit` is `1 / (1 + e^(-y))`, its coefficients are
+//! negated in the document.
+//!
+//! Documents written by this module can be imported again. Documents of other tools can be imported
+//! as long as they only use numeric predictors, and no XML features beyond elements, attributes,
+//! comments and CDATA sections.
+//!
+//! # Example
+//!
+//! ```
+//! use vikos::model::Linear;
+//! use vikos::pmml::{Fields, Pmml};
+//!
+//! let model = Linear { m: [0.5, -1.0], c: 2.0 };
+//! let fields = Fields::new(&["height", "age"], "weight");
+//! let document = model.to_pmml(&fields).unwrap();
+//!
+//! let (imported, imported_fields) = Linear::<[f64; 2]>::from_pmml(&document).unwrap();
+//! assert_eq!(model.m, imported.m);
+//! assert_eq!(fields, imported_fields);
+//! ```
+
+use crate::{
+    linear_algebra::Vector,
+    model::{Linear, Logistic, OneVsRest},
+    Model,
+};
+use std::{convert::TryFrom, error, fmt, fmt::Write};
+
+/// Names of the fields in a PMML document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields {
+    /// Name of each feature, in the order of the feature vector
+    pub features: Vec<String>,
+    /// Name of the target field
+    pub target: String,
+    /// Name of each class, in the order of the class indices. Empty for regressions.
+    pub classes: Vec<String>,
+}
+
+impl Fields {
+    /// Names for a regression, without classes
+    pub fn new(features: &[&str], target: &str) -> Fields {
+        Fields {
+            features: features.iter().map(|&name| name.to_string()).collect(),
+            target: target.to_string(),
+            classes: Vec::new(),
+        }
+    }
+
+    /// Names for a classification
+    pub fn with_classes(features: &[&str], target: &str, classes: &[&str]) -> Fields {
+        Fields {
+            classes: classes.iter().map(|&name| name.to_string()).collect(),
+            ..Fields::new(features, target)
+        }
+    }
+}
+
+/// Models which can be exported to and imported from PMML
+pub trait Pmml: Sized {
+    /// Writes the model as PMML document, using the names in `fields`
+    fn to_pmml(&self, fields: &Fields) -> Result<String, Error>;
+
+    /// Reads a model written by `to_pmml`, together with the names of its fields
+    fn from_pmml(document: &str) -> Result<(Self, Fields), Error>;
+}
+
+/// Errors occurring while exporting or importing PMML
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The document is not well formed XML
+    Xml(String),
+    /// A required element or attribute is missing
+    Missing(String),
+    /// The document describes a model which can not be represented by the requested type
+    Unsupported(String),
+    /// An attribute which should contain a number could not be parsed
+    InvalidNumber {
+        /// Name of the attribute
+        attribute: String,
+        /// Content of the attribute
+        value: String,
+    },
+    /// A predictor refers to a field which is not part of the mining schema
+    UnknownField(String),
+    /// Number of feature names does not match the feature dimension of the model
+    FieldCount {
+        /// Feature dimension of the model
+        expected: usize,
+        /// Number of feature names
+        found: usize,
+    },
+    /// Number of class names does not match the number of classes of the model
+    ClassCount {
+        /// Number of classes of the model
+        expected: usize,
+        /// Number of class names
+        found: usize,
+    },
+    /// A coefficient of the model is NaN or infinite, which PMML can not represent. Names the
+    /// coefficient, e.g. `intercept`.
+    NonFinite(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Xml(reason) => write!(f, "malformed XML: {}", reason),
+            Error::Missing(what) => write!(f, "missing {}", what),
+            Error::Unsupported(what) => write!(f, "unsupported model: {}", what),
+            Error::InvalidNumber { attribute, value } => write!(
+                f,
+                "could not parse '{}' in attribute '{}'",
+                value, attribute
+            ),
+            Error::UnknownField(name) => write!(f, "field '{}' not in mining schema", name),
+            Error::FieldCount { expected, found } => write!(
+                f,
+                "model expects {} features, but {} names were given",
+                expected, found
+            ),
+            Error::ClassCount { expected, found } => write!(
+                f,
+                "model distinguishes {} classes, but {} names were given",
+                expected, found
+            ),
+            Error::NonFinite(coefficient) => write!(f, "{} is not finite", coefficient),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Coefficients of one `RegressionTable`
+struct Table {
+    intercept: f64,
+    coefficients: Vec<f64>,
+}
+
+impl Table {
+    fn from_linear<V: Vector>(linear: &Linear<V>, sign: f64) -> Table {
+        // Adding zero turns `-0` into `0`, which reads better in the document
+        let scale = |x: f64| sign * x + 0.0;
+        Table {
+            intercept: scale(linear.c),
+            coefficients: (0..linear.m.dimension())
+                .map(|i| scale(linear.m.at(i)))
+                .collect(),
+        }
+    }
+
+    /// Sets the coefficients of `model`, which must consist of a linear term only
+    fn to_model<M: Model>(&self, model: &mut M, sign: f64) {
+        let n = self.coefficients.len();
+        for (i, &coefficient) in self.coefficients.iter().enumerate() {
+            *model.coefficient(i) = sign * coefficient;
+        }
+        *model.coefficient(n) = sign * self.intercept;
+    }
+}
+
+/// Content of a `RegressionModel`, independent of the model type
+struct Regression {
+    classification: bool,
+    normalization: &'static str,
+    /// One table for regressions, one table per class for classifications
+    tables: Vec<Table>,
+}
+
+/// Escapes text for use in an attribute value
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn write_document(regression: &Regression, fields: &Fields) -> Result<String, Error> {
+    let dimension = regression.tables[0].coefficients.len();
+    if fields.features.len() != dimension {
+        return Err(Error::FieldCount {
+            expected: dimension,
+            found: fields.features.len(),
+        });
+    }
+    let classes = if regression.classification {
+        regression.tables.len()
+    } else {
+        0
+    };
+    if fields.classes.len() != classes {
+        return Err(Error::ClassCount {
+            expected: classes,
+            found: fields.classes.len(),
+        });
+    }
+
+    for table in &regression.tables {
+        if !table.intercept.is_finite() {
+            return Err(Error::NonFinite("intercept".to_string()));
+        }
+        for (name, coefficient) in fields.features.iter().zip(&table.coefficients) {
+            if !coefficient.is_finite() {
+                return Err(Error::NonFinite(format!("coefficient of '{}'", name)));
+            }
+        }
+    }
+
+    let mut doc = String::new();
+    // Writing to a String can not fail
+    let mut line = |text: String| writeln!(doc, "{}", text).unwrap();
+    line(r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string());
+    line(r#"<PMML xmlns="http://www.dmg.org/PMML-4_4" version="4.4">"#.to_string());
+    line(format!(
+        r#"  <Header><Application name="vikos" version="{}"/></Header>"#,
+        env!("CARGO_PKG_VERSION")
+    ));
+    line(format!(
+        r#"  <DataDictionary numberOfFields="{}">"#,
+        dimension + 1
+    ));
+    for name in &fields.features {
+        line(format!(
+            r#"    <DataField name="{}" optype="continuous" dataType="double"/>"#,
+            escape(name)
+        ));
+    }
+    if regression.classification {
+        line(format!(
+            r#"    <DataField name="{}" optype="categorical" dataType="string">"#,
+            escape(&fields.target)
+        ));
+        for class in &fields.classes {
+            line(format!(r#"      <Value value="{}"/>"#, escape(class)));
+        }
+        line("    </DataField>".to_string());
+    } else {
+        line(format!(
+            r#"    <DataField name="{}" optype="continuous" dataType="double"/>"#,
+            escape(&fields.target)
+        ));
+    }
+    line("  </DataDictionary>".to_string());
+    line(format!(
+        r#"  <RegressionModel functionName="{}" normalizationMethod="{}">"#,
+        if regression.classification {
+            "classification"
+        } else {
+            "regression"
+        },
+        regression.normalization
+    ));
+    line("    <MiningSchema>".to_string());
+    for name in &fields.features {
+        line(format!(r#"      <MiningField name="{}"/>"#, escape(name)));
+    }
+    line(format!(
+        r#"      <MiningField name="{}" usageType="target"/>"#,
+        escape(&fields.target)
+    ));
+    line("    </MiningSchema>".to_string());
+    for (class, table) in regression.tables.iter().enumerate() {
+        let category = match regression.classification {
+            true => format!(r#" targetCategory="{}""#, escape(&fields.classes[class])),
+            false => String::new(),
+        };
+        line(format!(
+            r#"    <RegressionTable intercept="{}"{}>"#,
+            table.intercept, category
+        ));
+        for (name, coefficient) in fields.features.iter().zip(&table.coefficients) {
+            line(format!(
+                r#"      <NumericPredictor name="{}" coefficient="{}"/>"#,
+                escape(name),
+                coefficient
+            ));
+        }
+        line("    </RegressionTable>".to_string());
+    }
+    line("  </RegressionModel>".to_string());
+    line("</PMML>".to_string());
+    Ok(doc)
+}
+
+fn number(element: &Element, attribute: &str) -> Result<f64, Error> {
+    let value = element.required(attribute)?;
+    value.trim().parse().map_err(|_| Error::InvalidNumber {
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Reads the `RegressionModel` of `document` and checks it against the expected structure
+fn read_document(
+    document: &str,
+    classification: bool,
+    normalization: &str,
+) -> Result<(Regression, Fields), Error> {
+    let pmml = parse_xml(document)?;
+    if pmml.name != "PMML" {
+        return Err(Error::Missing("element 'PMML'".to_string()));
+    }
+    let model = pmml.required_child("RegressionModel")?;
+
+    let function = model.required("functionName")?;
+    let expected_function = if classification {
+        "classification"
+    } else {
+        "regression"
+    };
+    if function != expected_function {
+        return Err(Error::Unsupported(format!(
+            "expected functionName '{}', found '{}'",
+            expected_function, function
+        )));
+    }
+    let method = model.attribute("normalizationMethod").unwrap_or("none");
+    if method != normalization {
+        return Err(Error::Unsupported(format!(
+            "expected normalizationMethod '{}', found '{}'",
+            normalization, method
+        )));
+    }
+
+    let mut fields = Fields::new(&[], "");
+    let mut target = None;
+    for field in model
+        .required_child("MiningSchema")?
+        .children("MiningField")
+    {
+        let name = field.required("name")?.to_string();
+        match field.attribute("usageType").unwrap_or("active") {
+            "active" => fields.features.push(name),
+            "target" | "predicted" => target = Some(name),
+            _ => (),
+        }
+    }
+    fields.target = target.ok_or_else(|| Error::Missing("target field".to_string()))?;
+
+    let mut tables = Vec::new();
+    for element in model.children("RegressionTable") {
+        let mut table = Table {
+            intercept: number(element, "intercept")?,
+            coefficients: vec![0.0; fields.features.len()],
+        };
+        for predictor in element.children("NumericPredictor") {
+            let name = predictor.required("name")?;
+            let index = fields
+                .features
+                .iter()
+                .position(|feature| feature == name)
+                .ok_or_else(|| Error::UnknownField(name.to_string()))?;
+            if matches!(predictor.attribute("exponent"), Some(e) if e.trim() != "1") {
+                return Err(Error::Unsupported(format!(
+                    "exponent of predictor '{}'",
+                    name
+                )));
+            }
+            table.coefficients[index] = number(predictor, "coefficient")?;
+        }
+        if element.children("CategoricalPredictor").next().is_some()
+            || element.children("PredictorTerm").next().is_some()
+        {
+            return Err(Error::Unsupported(
+                "categorical predictors and predictor terms".to_string(),
+            ));
+        }
+        if classification {
+            fields
+                .classes
+                .push(element.required("targetCategory")?.to_string());
+        }
+        tables.push(table);
+    }
+    if tables.is_empty() {
+        return Err(Error::Missing("element 'RegressionTable'".to_string()));
+    }
+
+    let regression = Regression {
+        classification,
+        normalization: if normalization == "logit" {
+            "logit"
+        } else {
+            "none"
+        },
+        tables,
+    };
+    Ok((regression, fields))
+}
+
+/// Creates a vector of `dimension`, or reports an error if `V` has a different fixed dimension
+fn zero_vector<V: Vector>(dimension: usize) -> Result<V, Error> {
+    match V::fixed_dimension() {
+        Some(fixed) if fixed != dimension => Err(Error::FieldCount {
+            expected: fixed,
+            found: dimension,
+        }),
+        _ => Ok(V::zero_from_dimension(dimension)),
+    }
+}
+
+fn single_table(regression: Regression) -> Result<Table, Error> {
+    let mut tables = regression.tables;
+    if tables.len() != 1 {
+        return Err(Error::Unsupported(format!(
+            "expected a single RegressionTable, found {}",
+            tables.len()
+        )));
+    }
+    Ok(tables.remove(0))
+}
+
+impl<V: Vector> Pmml for Linear<V> {
+    fn to_pmml(&self, fields: &Fields) -> Result<String, Error> {
+        let regression = Regression {
+            classification: false,
+            normalization: "none",
+            tables: vec![Table::from_linear(self, 1.0)],
+        };
+        write_document(&regression, fields)
+    }
+
+    fn from_pmml(document: &str) -> Result<(Self, Fields), Error> {
+        let (regression, fields) = read_document(document, false, "none")?;
+        let mut model = Linear {
+            m: zero_vector(fields.features.len())?,
+            c: 0.0,
+        };
+        single_table(regression)?.to_model(&mut model, 1.0);
+        Ok((model, fields))
+    }
+}
+
+impl<V: Vector> Pmml for Logistic<V> {
+    fn to_pmml(&self, fields: &Fields) -> Result<String, Error> {
+        let regression = Regression {
+            classification: false,
+            normalization: "logit",
+            tables: vec![Table::from_linear(self.linear(), -1.0)],
+        };
+        write_document(&regression, fields)
+    }
+
+    fn from_pmml(document: &str) -> Result<(Self, Fields), Error> {
+        let (regression, fields) = read_document(document, false, "logit")?;
+        zero_vector::<V>(fields.features.len())?;
+        let mut model = Logistic::with_feature_dimension(fields.features.len());
+        single_table(regression)?.to_model(&mut model, -1.0);
+        Ok((model, fields))
+    }
+}
+
+impl<V: Vector, const N: usize> Pmml for OneVsRest<[Logistic<V>; N]> {
+    fn to_pmml(&self, fields: &Fields) -> Result<String, Error> {
+        let regression = Regression {
+            classification: true,
+            normalization: "logit",
+            tables: self
+                .models()
+                .iter()
+                .map(|model| Table::from_linear(model.linear(), -1.0))
+                .collect(),
+        };
+        write_document(&regression, fields)
+    }
+
+    fn from_pmml(document: &str) -> Result<(Self, Fields), Error> {
+        let (regression, fields) = read_document(document, true, "logit")?;
+        if regression.tables.len() != N {
+            return Err(Error::ClassCount {
+                expected: N,
+                found: regression.tables.len(),
+            });
+        }
+        let dimension = fields.features.len();
+        zero_vector::<V>(dimension)?;
+        let models: Vec<_> = regression
+            .tables
+            .iter()
+            .map(|table| {
+                let mut model = Logistic::with_feature_dimension(dimension);
+                table.to_model(&mut model, -1.0);
+                model
+            })
+            .collect();
+        match <[Logistic<V>; N]>::try_from(models) {
+            Ok(models) => Ok((OneVsRest::new(models), fields)),
+            Err(_) => unreachable!("number of tables has been checked"),
+        }
+    }
+}
+
+/// Element of an XML document. Text content is dropped.
+#[derive(Debug)]
+struct Element {
+    /// Local name, without namespace prefix
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, Error> {
+        self.attribute(name)
+            .ok_or_else(|| Error::Missing(format!("attribute '{}' of '{}'", name, self.name)))
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn required_child(&self, name: &str) -> Result<&Element, Error> {
+        self.children
+            .iter()
+            .find(|child| child.name == name)
+            .ok_or_else(|| Error::Missing(format!("element '{}'", name)))
+    }
+}
+
+/// Parses the subset of XML used by PMML documents: elements, attributes, text, CDATA sections,
+/// comments and processing instructions. Returns the root element.
+///
+/// A doctype declaration is skipped up to its first `>`, so internal subsets are not supported.
+/// Entities other than the predefined ones and character references are rejected.
+fn parse_xml(document: &str) -> Result<Element, Error> {
+    let mut parser = Parser { rest: document };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest.is_empty() {
+        return Err(Error::Xml("content after root element".to_string()));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_until(&mut self, end: &str) -> Result<&'a str, Error> {
+        let position = self
+            .rest
+            .find(end)
+            .ok_or_else(|| Error::Xml(format!("expected '{}'", end)))?;
+        let skipped = &self.rest[..position];
+        self.rest = &self.rest[position + end.len()..];
+        Ok(skipped)
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// Skips whitespace, comments, processing instructions and doctype declarations
+    fn skip_misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_whitespace();
+            if self.rest.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if self.rest.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.rest.starts_with("<!") {
+                self.skip_until(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(Error::Xml("expected name".to_string()));
+        }
+        let name = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        // Drop namespace prefix
+        Ok(name.rsplit(':').next().unwrap_or(name).to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, Error> {
+        if !self.rest.starts_with('<') {
+            return Err(Error::Xml("expected element".to_string()));
+        }
+        self.rest = &self.rest[1..];
+        let mut element = Element {
+            name: self.name()?,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        };
+        loop {
+            self.skip_whitespace();
+            if self.rest.starts_with("/>") {
+                self.rest = &self.rest[2..];
+                return Ok(element);
+            } else if self.rest.starts_with('>') {
+                self.rest = &self.rest[1..];
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            if !self.rest.starts_with('=') {
+                return Err(Error::Xml(format!("expected '=' after '{}'", key)));
+            }
+            self.rest = self.rest[1..].trim_start();
+            let quote = match self.rest.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => return Err(Error::Xml(format!("expected quoted value of '{}'", key))),
+            };
+            self.rest = &self.rest[1..];
+            let value = self.skip_until(&quote.to_string())?;
+            element.attributes.push((key, unescape(value)?));
+        }
+
+        loop {
+            if self.rest.starts_with("</") {
+                self.rest = &self.rest[2..];
+                let name = self.name()?;
+                self.skip_whitespace();
+                self.skip_until(">")?;
+                if name != element.name {
+                    return Err(Error::Xml(format!(
+                        "'{}' closed by '{}'",
+                        element.name, name
+                    )));
+                }
+                return Ok(element);
+            } else if self.rest.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.rest.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if self.rest.starts_with("<![CDATA[") {
+                // Text content is not used by any supported element
+                self.skip_until("]]>")?;
+            } else if self.rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else if self.rest.is_empty() {
+                return Err(Error::Xml(format!("'{}' not closed", element.name)));
+            } else {
+                // Text content is not used by any supported element
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                self.rest = &self.rest[end..];
+            }
+        }
+    }
+}
+
+/// Replaces the predefined entities and character references
+fn unescape(text: &str) -> Result<String, Error> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| Error::Xml("unterminated entity".to_string()))?;
+        let entity = &rest[..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(std::char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        result.push(character.ok_or_else(|| Error::Xml(format!("unknown entity '{}'", entity)))?);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{parse_xml, unescape, Error, Fields, Pmml};
+    use crate::model::{Linear, Logistic, OneVsRest};
+    use crate::Model;
+
+    #[test]
+    fn linear_round_trip() {
+        let model = Linear {
+            m: vec![0.1, -2.5, 3e-7],
+            c: 1.0 / 3.0,
+        };
+        let fields = Fields::new(&["a", "b & c", "\"d\""], "y");
+        let document = model.to_pmml(&fields).unwrap();
+        assert!(document.contains(r#"<MiningField name="b &amp; c"/>"#));
+        assert!(document.contains(r#"normalizationMethod="none""#));
+
+        let (imported, imported_fields) = Linear::<Vec<f64>>::from_pmml(&document).unwrap();
+        assert_eq!(model.m, imported.m);
+        assert_eq!(model.c, imported.c);
+        assert_eq!(fields, imported_fields);
+
+        assert_eq!(
+            Err(Error::FieldCount {
+                expected: 2,
+                found: 3
+            }),
+            Linear::<[f64; 2]>::from_pmml(&document).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn logistic_round_trip() {
+        let mut model = Logistic::default();
+        *model.coefficient(0) = 0.25;
+        *model.coefficient(1) = -1.5;
+        *model.coefficient(2) = 0.75;
+        let fields = Fields::new(&["x0", "x1"], "p");
+        let document = model.to_pmml(&fields).unwrap();
+        assert!(document.contains(r#"normalizationMethod="logit""#));
+        // PMML's logit has the opposite sign
+        assert!(document.contains(r#"<RegressionTable intercept="-0.75">"#));
+
+        let (imported, _) = Logistic::<[f64; 2]>::from_pmml(&document).unwrap();
+        let input = [0.3, 1.2];
+        assert_eq!(model.predict(&input), imported.predict(&input));
+
+        // A linear model can not be read from a logistic document
+        match Linear::<[f64; 2]>::from_pmml(&document) {
+            Err(Error::Unsupported(_)) => (),
+            other => panic!("expected unsupported model, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn one_vs_rest_round_trip() {
+        let mut model = OneVsRest::<[Logistic<[f64; 2]>; 3]>::default();
+        for i in 0..model.num_coefficients() {
+            *model.coefficient(i) = 0.5 * i as f64 - 1.0;
+        }
+        let fields = Fields::with_classes(&["x0", "x1"], "species", &["a", "b", "c"]);
+        let document = model.to_pmml(&fields).unwrap();
+        assert_eq!(3, document.matches("targetCategory").count());
+
+        let (imported, imported_fields) =
+            OneVsRest::<[Logistic<[f64; 2]>; 3]>::from_pmml(&document).unwrap();
+        let input = [-0.4, 2.0];
+        assert_eq!(model.predict(&input), imported.predict(&input));
+        assert_eq!(fields, imported_fields);
+
+        assert_eq!(
+            Err(Error::ClassCount {
+                expected: 2,
+                found: 3
+            }),
+            OneVsRest::<[Logistic<[f64; 2]>; 2]>::from_pmml(&document).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::ClassCount {
+                expected: 3,
+                found: 2
+            }),
+            model
+                .to_pmml(&Fields::with_classes(&["x0", "x1"], "y", &["a", "b"]))
+                .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn reject_non_finite() {
+        let fields = Fields::new(&["a", "b"], "y");
+        let model = Linear {
+            m: [1.0, f64::NAN],
+            c: 0.0,
+        };
+        assert_eq!(
+            "coefficient of 'b' is not finite",
+            model.to_pmml(&fields).unwrap_err().to_string()
+        );
+        let model = Linear {
+            m: [1.0, 2.0],
+            c: f64::INFINITY,
+        };
+        assert_eq!(
+            Err(Error::NonFinite("intercept".to_string())),
+            model.to_pmml(&fields)
+        );
+    }
+
+    #[test]
+    fn import_foreign_document() {
+        // Predictors may be omitted or listed in a different order than the mining schema
+        let document = r#"<?xml version="1.0"?>
+            <!-- written by hand -->
+            <pmml:PMML xmlns:pmml="http://www.dmg.org/PMML-4_4" version="4.4">
+              <pmml:RegressionModel functionName='regression'>
+                <pmml:Extension><![CDATA[ <not an="element"> ]]></pmml:Extension>
+                <pmml:MiningSchema>
+                  <pmml:MiningField name="x"/>
+                  <pmml:MiningField name="y"/>
+                  <pmml:MiningField name="z" usageType="predicted"/>
+                </pmml:MiningSchema>
+                <pmml:RegressionTable intercept="1.5">
+                  <pmml:NumericPredictor name="y" coefficient="2"/>
+                </pmml:RegressionTable>
+              </pmml:RegressionModel>
+            </pmml:PMML>"#;
+        let (model, fields) = Linear::<[f64; 2]>::from_pmml(document).unwrap();
+        assert_eq!([0.0, 2.0], model.m);
+        assert_eq!(1.5, model.c);
+        assert_eq!("z", fields.target);
+    }
+
+    #[test]
+    fn malformed_documents() {
+        assert!(matches!(
+            parse_xml("<PMML><Header></PMML>"),
+            Err(Error::Xml(_))
+        ));
+        assert!(matches!(parse_xml("<PMML a=1/>"), Err(Error::Xml(_))));
+        assert_eq!(
+            "missing element 'RegressionModel'",
+            Linear::<[f64; 1]>::from_pmml("<PMML/>")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "<a> & 'b' A",
+            unescape("&lt;a&gt; &amp; &apos;b&apos; &#65;").unwrap()
+        );
+    }
+}
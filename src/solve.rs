@@ -0,0 +1,646 @@
+//! Fitting models from a whole history at once
+//!
+//! Unlike teachers, which learn one event at a time, the solvers in this module look at the entire
+//! history at once and need no learning rate. `least_squares` and `ridge` fit `Linear` models in
+//! closed form, `irls` fits `Logistic` and generalized linear models with Newton's method. For
+//! moderately sized problems they yield the exact optimum, which is also useful as a baseline for
+//! models trained with stochastic gradient descent.
+
+use crate::dual::Dual;
+use crate::linear_algebra::Vector;
+use crate::model::{GeneralizedLinearModel, Linear, Logistic};
+use crate::{Model, SecondDerivative};
+use std::{error, fmt};
+
+/// Errors occurring while solving for the coefficients of a model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The history did not contain any events
+    Empty,
+    /// The features of an event have a different dimension than those of the first event
+    DimensionMismatch {
+        /// Position of the event in the history
+        position: usize,
+        /// Dimension of the features of the first event
+        expected: usize,
+        /// Dimension of the features of the event at `position`
+        found: usize,
+    },
+    /// The ridge penalty is negative or not finite
+    InvalidPenalty(f64),
+    /// The normal equations have no unique solution
+    ///
+    /// The feature at `coefficient` is constant or a linear combination of the preceding ones. An
+    /// index equal to the feature dimension denotes the offset. A positive ridge penalty makes the
+    /// system solvable, unless the offset is the culprit.
+    Singular {
+        /// Index of the coefficient which could not be determined
+        coefficient: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "history is empty"),
+            Error::DimensionMismatch {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "features of event {} have dimension {}, expected {}",
+                position, found, expected
+            ),
+            Error::InvalidPenalty(penalty) => write!(f, "invalid ridge penalty {}", penalty),
+            Error::Singular { coefficient } => write!(
+                f,
+                "normal equations are singular, coefficient {} is not determined",
+                coefficient
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Fits a `Linear` model minimizing the sum of squared errors over `history`
+///
+/// Equivalent to `ridge` with a penalty of zero.
+///
+/// # Example
+///
+/// ```
+/// use vikos::solve::least_squares;
+///
+/// let history = vec![([0.0, 1.0], 4.0), ([1.0, 0.0], 3.0), ([1.0, 1.0], 6.0), ([2.0, 1.0], 8.0)];
+/// let model = least_squares(history).unwrap();
+/// assert!((model.m[0] - 2.0).abs() < 1e-9);
+/// assert!((model.m[1] - 3.0).abs() < 1e-9);
+/// assert!((model.c - 1.0).abs() < 1e-9);
+/// ```
+pub fn least_squares<V, I>(history: I) -> Result<Linear<V>, Error>
+where
+    V: Vector,
+    I: IntoIterator<Item = (V, f64)>,
+{
+    ridge(history, 0.0)
+}
+
+/// Fits a `Linear` model minimizing the sum of squared errors plus `penalty` times the squared
+/// norm of the slope
+///
+/// The offset is not penalized. The normal equations are accumulated in a single pass over
+/// `history` and solved with a Cholesky decomposition. Memory and time grow quadratically with the
+/// feature dimension, so this is meant for dense problems with a moderate number of features.
+pub fn ridge<V, I>(history: I, penalty: f64) -> Result<Linear<V>, Error>
+where
+    V: Vector,
+    I: IntoIterator<Item = (V, f64)>,
+{
+    if !(penalty >= 0.0 && penalty.is_finite()) {
+        return Err(Error::InvalidPenalty(penalty));
+    }
+    let mut history = history.into_iter();
+    let (features, truth) = history.next().ok_or(Error::Empty)?;
+    let dimension = features.dimension();
+    // Offset is the last coefficient, just like in `Linear::coefficient`
+    let n = dimension + 1;
+    let mut normal = NormalEquations::new(n);
+    normal.add(&features, 1.0, truth);
+
+    for (position, (features, truth)) in history.enumerate() {
+        if features.dimension() != dimension {
+            return Err(Error::DimensionMismatch {
+                position: position + 1,
+                expected: dimension,
+                found: features.dimension(),
+            });
+        }
+        normal.add(&features, 1.0, truth);
+    }
+
+    for i in 0..dimension {
+        normal.matrix[i * n + i] += penalty;
+    }
+    let solution = normal.solve()?;
+
+    let mut model = Linear::<V>::with_feature_dimension(dimension);
+    for (i, &value) in solution[..dimension].iter().enumerate() {
+        *model.m.at_mut(i) = value;
+    }
+    model.c = solution[dimension];
+    Ok(model)
+}
+
+/// Models predicting `g(m * x + c)` for a `Linear` term `m * x + c`
+///
+/// The coefficients of the model must be those of the linear term, in the same order. Implemented
+/// for the models which can be fitted with `irls`.
+pub trait LinearPredictor: Model<Target = f64> {
+    /// Linear term `m * x + c`
+    fn linear(&self) -> &Linear<Self::Features>;
+
+    /// Value, first and second derivative of `g` at `eta`
+    fn inverse_link(&self, eta: f64) -> (f64, f64, f64);
+}
+
+impl<V: Vector> LinearPredictor for Linear<V> {
+    fn linear(&self) -> &Linear<V> {
+        self
+    }
+
+    fn inverse_link(&self, eta: f64) -> (f64, f64, f64) {
+        (eta, 1.0, 0.0)
+    }
+}
+
+impl<V: Vector> LinearPredictor for Logistic<V> {
+    fn linear(&self) -> &Linear<V> {
+        Logistic::linear(self)
+    }
+
+    fn inverse_link(&self, eta: f64) -> (f64, f64, f64) {
+        let p = 1.0 / (1.0 + eta.exp());
+        let derivative = -p * (1.0 - p);
+        (p, derivative, -derivative * (1.0 - 2.0 * p))
+    }
+}
+
+impl<V, G> LinearPredictor for GeneralizedLinearModel<V, G>
+where
+    V: Vector,
+    G: Fn(Dual) -> Dual,
+{
+    fn linear(&self) -> &Linear<V> {
+        &self.linear
+    }
+
+    /// The second derivative is approximated by a central difference of the first one
+    ///
+    /// With a step of `1e-5·(1 + |eta|)` its relative error is about `1e-10` for smooth links,
+    /// limited by rounding. `g''` only enters the curvature, so the error may slow down `irls`
+    /// slightly, but does not move the optimum it converges to.
+    fn inverse_link(&self, eta: f64) -> (f64, f64, f64) {
+        let g = &self.g;
+        let at = g(Dual::variable(eta));
+        let h = 1e-5 * (1.0 + eta.abs());
+        let second = (g(Dual::variable(eta + h)).derivative
+            - g(Dual::variable(eta - h)).derivative)
+            / (2.0 * h);
+        (at.value, at.derivative, second)
+    }
+}
+
+/// Outcome of `irls`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// Number of Newton steps taken
+    pub iterations: usize,
+    /// Summed cost of all events after the last step
+    ///
+    /// For `cost::LeastSquares` this is the residual sum of squares, for `cost::MaxLikelihood` the
+    /// negative log likelihood, i.e. half of the binomial deviance.
+    pub deviance: f64,
+    /// `false` if `max_iterations` has been reached before the deviance converged, or if no step
+    /// along the Newton direction lowered the deviance
+    pub converged: bool,
+}
+
+/// Fits `model` to `history` with Newton's method, also known as iteratively reweighted least
+/// squares
+///
+/// Each step solves the normal equations of a least squares problem, in which every event is
+/// weighted with the curvature of its cost `c''(g)·g'² + c'(g)·g''`. For `Logistic` with
+/// `cost::MaxLikelihood` this is the familiar `p·(1 - p)`. Steps which would increase the deviance
+/// are halved. Iteration stops once the relative change of the deviance
+/// `|deviance - previous| / (|deviance| + 0.1)` is below `tolerance`, after `max_iterations`
+/// steps, or if not even a step halved 30 times improves the deviance. Only the first case is
+/// reported as converged. The current coefficients of `model` serve as starting point.
+///
+/// With a link function other than the canonical one for `cost`, the curvature may be negative far
+/// from the optimum. Such events are weighted with the expected curvature `c''(g)·g'²` instead
+/// (Fisher scoring), which is never negative for a convex cost. This only changes the steps, not
+/// the optimum they lead to.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, model, solve, Model};
+///
+/// let history = [
+///     ([2.7, 2.5], false),
+///     ([1.4, 2.3], false),
+///     ([3.3, 4.4], true),
+///     ([1.3, 1.8], false),
+///     ([3.0, 3.0], false),
+///     ([7.6, 2.7], true),
+///     ([5.3, 2.0], true),
+///     ([6.9, 1.7], false),
+///     ([8.6, -0.2], true),
+///     ([7.6, 3.5], true),
+/// ];
+/// let mut model = model::Logistic::default();
+/// let report = solve::irls(&cost::MaxLikelihood {}, &mut model, &history, 1e-8, 25).unwrap();
+/// assert!(report.converged);
+/// println!("{} iterations, deviance {}", report.iterations, report.deviance);
+/// println!("{}", model.predict(&[5.0, 2.5]));
+/// ```
+pub fn irls<M, C, Truth>(
+    cost: &C,
+    model: &mut M,
+    history: &[(M::Features, Truth)],
+    tolerance: f64,
+    max_iterations: usize,
+) -> Result<Report, Error>
+where
+    M: LinearPredictor,
+    M::Features: Vector,
+    C: SecondDerivative<Truth>,
+    Truth: Copy,
+{
+    if history.is_empty() {
+        return Err(Error::Empty);
+    }
+    let dimension = model.linear().m.dimension();
+    if let Some(position) = history
+        .iter()
+        .position(|(features, _)| features.dimension() != dimension)
+    {
+        return Err(Error::DimensionMismatch {
+            position,
+            expected: dimension,
+            found: history[position].0.dimension(),
+        });
+    }
+    let n = dimension + 1;
+
+    let deviance_of = |model: &M| -> f64 {
+        history
+            .iter()
+            .map(|(features, truth)| cost.cost(model.predict(features), *truth))
+            .sum()
+    };
+    let mut deviance = deviance_of(model);
+
+    for iteration in 1..=max_iterations {
+        // The right hand side is the negative gradient, so the solution is the Newton step
+        let mut normal = NormalEquations::new(n);
+        for (features, truth) in history {
+            let (prediction, first, second) = model.inverse_link(model.linear().predict(features));
+            let outer = cost.outer_derivative(&prediction, *truth);
+            let curvature = cost.second_derivative(prediction, *truth);
+            let fisher = curvature * first * first;
+            let observed = fisher + outer * second;
+            // Negative curvature would turn the step uphill, so use the expected one instead
+            let weight = if observed > 0.0 { observed } else { fisher };
+            normal.add(features, weight, -outer * first);
+        }
+        let step = normal.solve()?;
+
+        let start: Vec<f64> = (0..n).map(|ci| *model.coefficient(ci)).collect();
+        let mut scale = 1.0;
+        let mut improved = None;
+        for _ in 0..30 {
+            for (ci, (start, step)) in start.iter().zip(&step).enumerate() {
+                *model.coefficient(ci) = start + scale * step;
+            }
+            let candidate = deviance_of(model);
+            if candidate <= deviance {
+                improved = Some(candidate);
+                break;
+            }
+            scale *= 0.5;
+        }
+        let next = match improved {
+            Some(next) => next,
+            None => {
+                // Even a tiny step does not improve the fit. Either the curvature does not describe
+                // the cost, or the equations are too ill-conditioned to make progress.
+                for (ci, &start) in start.iter().enumerate() {
+                    *model.coefficient(ci) = start;
+                }
+                return Ok(Report {
+                    iterations: iteration - 1,
+                    deviance,
+                    converged: false,
+                });
+            }
+        };
+
+        let change = (deviance - next).abs() / (next.abs() + 0.1);
+        deviance = next;
+        if change < tolerance {
+            return Ok(Report {
+                iterations: iteration,
+                deviance,
+                converged: true,
+            });
+        }
+    }
+
+    Ok(Report {
+        iterations: max_iterations,
+        deviance,
+        converged: false,
+    })
+}
+
+/// `XᵀX` and `Xᵀy` of a design matrix `X`, whose last column is constant one
+struct NormalEquations {
+    n: usize,
+    /// Row major, only the lower triangle is filled
+    matrix: Vec<f64>,
+    rhs: Vec<f64>,
+}
+
+impl NormalEquations {
+    fn new(n: usize) -> Self {
+        NormalEquations {
+            n,
+            matrix: vec![0.0; n * n],
+            rhs: vec![0.0; n],
+        }
+    }
+
+    /// Adds `weight * x * xᵀ` to the matrix and `target * x` to the right hand side
+    fn add<V: Vector>(&mut self, features: &V, weight: f64, target: f64) {
+        let n = self.n;
+        let x = |i: usize| if i + 1 == n { 1.0 } else { features.at(i) };
+        for i in 0..n {
+            let xi = x(i);
+            if xi == 0.0 {
+                continue;
+            }
+            for j in 0..=i {
+                self.matrix[i * n + j] += weight * xi * x(j);
+            }
+            self.rhs[i] += target * xi;
+        }
+    }
+
+    /// Solves the equations in place by a Cholesky decomposition
+    fn solve(mut self) -> Result<Vec<f64>, Error> {
+        let n = self.n;
+        let a = &mut self.matrix;
+        for j in 0..n {
+            let diagonal = a[j * n + j];
+            let pivot = diagonal - (0..j).map(|k| a[j * n + k] * a[j * n + k]).sum::<f64>();
+            // Relative to the diagonal, so the test does not depend on the scale of the feature
+            if pivot.is_nan() || pivot <= 1e-10 * diagonal {
+                return Err(Error::Singular { coefficient: j });
+            }
+            let pivot = pivot.sqrt();
+            a[j * n + j] = pivot;
+            for i in j + 1..n {
+                let sum = (0..j).map(|k| a[i * n + k] * a[j * n + k]).sum::<f64>();
+                a[i * n + j] = (a[i * n + j] - sum) / pivot;
+            }
+        }
+
+        // Forward substitution L z = b, followed by back substitution Lᵀ x = z
+        let mut x = self.rhs;
+        for i in 0..n {
+            let sum = (0..i).map(|k| a[i * n + k] * x[k]).sum::<f64>();
+            x[i] = (x[i] - sum) / a[i * n + i];
+        }
+        for i in (0..n).rev() {
+            let sum = (i + 1..n).map(|k| a[k * n + i] * x[k]).sum::<f64>();
+            x[i] = (x[i] - sum) / a[i * n + i];
+        }
+        Ok(x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{irls, least_squares, ridge, Error};
+    use crate::cost::{LeastSquares, MaxLikelihood};
+    use crate::dual::Number;
+    use crate::linear_algebra::SparseVector;
+    use crate::model::{GeneralizedLinearModel, Linear, Logistic};
+    use crate::{Cost, Model, SecondDerivative};
+
+    fn overlapping_classes() -> Vec<([f64; 2], bool)> {
+        (0..40)
+            .map(|i| {
+                let x = [(i % 8) as f64 - 3.5, (i % 5) as f64 - 2.0];
+                // Noisy labels, so the classes are not linearly separable
+                (x, x[0] + 0.5 * x[1] + [-1.0, 2.0, 0.0][i % 3] > 0.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_fit() {
+        let history: Vec<_> = (0..20)
+            .map(|i| {
+                let x = [(i % 4) as f64, (i % 7) as f64 - 3.0, (i * i % 5) as f64];
+                (x, 0.5 * x[0] - 2.0 * x[1] + x[2] + 4.0)
+            })
+            .collect();
+        let model = least_squares(history).unwrap();
+        for (found, expected) in model.m.iter().zip(&[0.5, -2.0, 1.0]) {
+            assert!((found - expected).abs() < 1e-9);
+        }
+        assert!((model.c - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ridge_shrinks_slope() {
+        // Single feature: slope is Σxy / (Σx² + penalty) for centered data
+        let history = vec![(-1.0, -2.0), (0.0, 0.0), (1.0, 2.0)];
+        let model = ridge(history.clone(), 2.0).unwrap();
+        assert!((model.m - 1.0).abs() < 1e-12);
+        assert!(model.c.abs() < 1e-12);
+        assert!((least_squares(history).unwrap().m - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sparse_features() {
+        let history: Vec<_> = (0..10)
+            .map(|i| {
+                let features =
+                    SparseVector::from_entries(4, vec![(i % 4, 1.0), ((i + 1) % 4, i as f64)]);
+                let truth = 3.0 * features.entries().iter().map(|&(_, v)| v).sum::<f64>() - 1.0;
+                (features, truth)
+            })
+            .collect();
+        let model = least_squares(history).unwrap();
+        for &(_, slope) in model.m.entries() {
+            assert!((slope - 3.0).abs() < 1e-9);
+        }
+        assert!((model.c + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(Error::Empty),
+            least_squares(Vec::<([f64; 1], f64)>::new()).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::DimensionMismatch {
+                position: 1,
+                expected: 2,
+                found: 1
+            }),
+            least_squares(vec![(vec![1.0, 2.0], 1.0), (vec![1.0], 2.0)]).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::InvalidPenalty(-1.0)),
+            ridge(vec![(1.0, 1.0)], -1.0).map(|_| ())
+        );
+
+        // Second feature is twice the first one
+        let collinear: Vec<_> = (0..5)
+            .map(|i| ([i as f64, 2.0 * i as f64], i as f64))
+            .collect();
+        let singular = least_squares(collinear.clone()).map(|_| ());
+        assert_eq!(Err(Error::Singular { coefficient: 1 }), singular);
+        assert_eq!(
+            "normal equations are singular, coefficient 1 is not determined",
+            singular.unwrap_err().to_string()
+        );
+        assert!(ridge(collinear, 0.1).is_ok());
+
+        // Constant feature can not be told apart from the offset
+        let constant = vec![([1.0], 1.0), ([1.0], 2.0)];
+        assert_eq!(
+            Err(Error::Singular { coefficient: 1 }),
+            least_squares(constant).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn irls_linear_equals_least_squares() {
+        let history: Vec<_> = (0..15).map(|i| (i as f64, (i * i % 7) as f64)).collect();
+        let exact = least_squares(history.clone()).unwrap();
+        let mut model = Linear { m: 0.0, c: 0.0 };
+        let report = irls(&LeastSquares {}, &mut model, &history, 1e-10, 10).unwrap();
+        assert!(report.converged);
+        assert!(report.iterations <= 2);
+        assert!((model.m - exact.m).abs() < 1e-9);
+        assert!((model.c - exact.c).abs() < 1e-9);
+    }
+
+    #[test]
+    fn irls_logistic() {
+        let history = overlapping_classes();
+        let mut model = Logistic::<[f64; 2]>::default();
+        let report = irls(&MaxLikelihood {}, &mut model, &history, 1e-12, 25).unwrap();
+        assert!(report.converged);
+        assert!(report.iterations < 10);
+
+        // Gradient of the summed cost vanishes at the optimum
+        let cost = MaxLikelihood {};
+        for ci in 0..model.num_coefficients() {
+            let gradient: f64 = history
+                .iter()
+                .map(|(x, truth)| {
+                    cost.outer_derivative(&model.predict(x), *truth) * model.gradient(ci, x)
+                })
+                .sum();
+            assert!(gradient.abs() < 1e-6);
+        }
+        let deviance: f64 = history
+            .iter()
+            .map(|(x, truth)| cost.cost(model.predict(x), *truth))
+            .sum();
+        assert!((report.deviance - deviance).abs() < 1e-12);
+    }
+
+    #[test]
+    fn irls_generalized_linear_model() {
+        let history = overlapping_classes();
+        let mut logistic = Logistic::<[f64; 2]>::default();
+        irls(&MaxLikelihood {}, &mut logistic, &history, 1e-12, 25).unwrap();
+
+        let mut glm = GeneralizedLinearModel::new(|x| 1.0 / (1.0 + x.exp()));
+        let report = irls(&MaxLikelihood {}, &mut glm, &history, 1e-12, 25).unwrap();
+        assert!(report.converged);
+        for (a, b) in glm.linear.m.iter().zip(&logistic.linear().m) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        assert!((glm.linear.c - logistic.linear().c).abs() < 1e-6);
+    }
+
+    #[test]
+    fn irls_non_canonical_link() {
+        // Least squares with a logistic link. Starting with saturated predictions, the observed
+        // curvature of most events is negative.
+        let history: Vec<_> = (0..20)
+            .map(|i| {
+                let x = i as f64 / 4.0 - 2.5;
+                (
+                    x,
+                    1.0 / (1.0 + (1.5 * x - 0.5).exp()) + 0.05 * (i % 3) as f64,
+                )
+            })
+            .collect();
+        let mut glm = GeneralizedLinearModel::new(|x| 1.0 / (1.0 + x.exp()));
+        glm.linear.m = -8.0;
+        glm.linear.c = 4.0;
+        let report = irls(&LeastSquares {}, &mut glm, &history, 1e-12, 100).unwrap();
+        assert!(report.converged);
+
+        // Gradient of the summed cost vanishes at the optimum
+        let cost = LeastSquares {};
+        for ci in 0..glm.num_coefficients() {
+            let gradient: f64 = history
+                .iter()
+                .map(|(x, truth)| {
+                    cost.outer_derivative(&glm.predict(x), *truth) * glm.gradient(ci, x)
+                })
+                .sum();
+            assert!(gradient.abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn irls_reports_failed_step() {
+        // Derivative with the wrong sign, which turns every Newton step uphill
+        struct Misleading;
+
+        impl Cost<f64> for Misleading {
+            fn outer_derivative(&self, prediction: &f64, truth: f64) -> f64 {
+                -LeastSquares {}.outer_derivative(prediction, truth)
+            }
+
+            fn cost(&self, prediction: f64, truth: f64) -> f64 {
+                LeastSquares {}.cost(prediction, truth)
+            }
+        }
+
+        impl SecondDerivative<f64> for Misleading {
+            fn second_derivative(&self, prediction: f64, truth: f64) -> f64 {
+                LeastSquares {}.second_derivative(prediction, truth)
+            }
+        }
+
+        let history = [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)];
+        let mut model = Linear { m: 0.0, c: 0.0 };
+        let report = irls(&Misleading, &mut model, &history, 1e-12, 10).unwrap();
+        assert!(!report.converged);
+        assert_eq!(0, report.iterations);
+        // The model is left at its starting point
+        assert_eq!(0.0, model.m);
+        assert_eq!(0.0, model.c);
+    }
+
+    #[test]
+    fn irls_reports_missing_convergence() {
+        // Separable classes, coefficients grow without bounds
+        let history = [(-1.0, false), (-0.5, false), (0.5, true), (1.0, true)];
+        let mut model = Logistic::<f64>::default();
+        let report = irls(&MaxLikelihood {}, &mut model, &history, 1e-12, 3).unwrap();
+        assert!(!report.converged);
+        assert_eq!(3, report.iterations);
+
+        assert_eq!(
+            Err(Error::Empty),
+            irls::<_, _, bool>(&MaxLikelihood {}, &mut model, &[], 1e-8, 3)
+        );
+    }
+}
@@ -12,18 +12,65 @@ fn annealed_learning_rate(num_events: usize, start: f64, t: f64) -> f64 {
     start / (1.0 + num_events as f64 / t)
 }
 
-/// Value of the gradient of the cost function (i.e. the cost function derived by the n-th
-/// coefficient at x expressed in Error(x) and dY(x)/dx
+/// Gradient of the cost function for a single event
 ///
-/// This method is called by stochastic gradient descent (SGD)-based training algorithm in order to
-/// determine the delta of the coefficients
-fn gradient<T, P, C>(cost: &C, prediction: &P, truth: T, derivative_of_model: &P) -> f64
+/// Applies the outer derivative of the cost function to the derivative of the model (chain rule).
+/// This is used by stochastic gradient descent (SGD)-based training algorithms in order to
+/// determine the delta of the coefficients.
+struct EventGradient<'a, M: Model> {
+    features: &'a M::Features,
+    outer_derivative: M::Target,
+    /// Derivatives of the model, if it provides them all at once
+    derivatives: Option<Vec<M::Target>>,
+}
+
+impl<'a, M> EventGradient<'a, M>
 where
-    C: Cost<T, P>,
-    P: Vector,
+    M: Model,
+    M::Target: Vector,
 {
-    cost.outer_derivative(&prediction, truth)
-        .dot(derivative_of_model)
+    fn new<Y, C>(cost: &C, model: &M, features: &'a M::Features, truth: Y) -> Self
+    where
+        C: Cost<Y, M::Target>,
+    {
+        let outer_derivative = cost.outer_derivative(&model.predict(features), truth);
+        Self::with_outer_derivative(outer_derivative, model, features)
+    }
+
+    /// Uses an `outer_derivative` of the cost function determined beforehand, e.g. at a
+    /// different position of `model`
+    fn with_outer_derivative(
+        outer_derivative: M::Target,
+        model: &M,
+        features: &'a M::Features,
+    ) -> Self {
+        EventGradient {
+            features,
+            outer_derivative,
+            derivatives: model.gradients(features),
+        }
+    }
+
+    /// Cost function derived by the `ci`-th coefficient of `model`
+    fn at(&self, model: &M, ci: usize) -> f64 {
+        match self.derivatives {
+            Some(ref derivatives) => self.outer_derivative.dot(&derivatives[ci]),
+            None => self
+                .outer_derivative
+                .dot(&model.gradient(ci, self.features)),
+        }
+    }
+}
+
+/// A `Teacher` whose changes to the coefficients only depend on the gradient of the cost function
+///
+/// Implementing this trait allows decorators like `GradientClipping` to alter the gradient before
+/// it is applied to the `Model`.
+pub trait GradientTeacher<M: Model>: Teacher<M> {
+    /// Changes `model`s coefficients using the `gradient` of the cost function
+    ///
+    /// `gradient` holds one element for each coefficient of `model`.
+    fn teach_gradient(&self, training: &mut Self::Training, model: &mut M, gradient: &[f64]);
 }
 
 /// Gradient descent
@@ -55,12 +102,30 @@ where
         C: Cost<Y, M::Target>,
         Y: Copy,
     {
-        let prediction = model.predict(features);
+        let gradient = EventGradient::new(cost, model, features, truth);
+        self.descend(model, |model, ci| gradient.at(model, ci));
+    }
+}
+
+impl<M> GradientTeacher<M> for GradientDescent
+where
+    M: Model,
+    M::Target: Vector,
+{
+    fn teach_gradient(&self, _training: &mut (), model: &mut M, gradient: &[f64]) {
+        self.descend(model, |_, ci| gradient[ci]);
+    }
+}
 
+impl GradientDescent {
+    /// Changes each coefficient of `model` by the value returned by `gradient` for it
+    fn descend<M, G>(&self, model: &mut M, gradient: G)
+    where
+        M: Model,
+        G: Fn(&M, usize) -> f64,
+    {
         for ci in 0..model.num_coefficients() {
-            *model.coefficient(ci) = *model.coefficient(ci)
-                - self.learning_rate
-                    * gradient(cost, &prediction, truth, &model.gradient(ci, features));
+            *model.coefficient(ci) -= self.learning_rate * gradient(model, ci);
         }
     }
 }
@@ -101,12 +166,32 @@ where
         C: Cost<Y, M::Target>,
         Y: Copy,
     {
-        let prediction = model.predict(features);
+        let gradient = EventGradient::new(cost, model, features, truth);
+        self.descend(num_events, model, |model, ci| gradient.at(model, ci));
+    }
+}
+
+impl<M> GradientTeacher<M> for GradientDescentAl
+where
+    M: Model,
+    M::Target: Vector,
+{
+    fn teach_gradient(&self, num_events: &mut usize, model: &mut M, gradient: &[f64]) {
+        self.descend(num_events, model, |_, ci| gradient[ci]);
+    }
+}
+
+impl GradientDescentAl {
+    /// Changes each coefficient of `model` by the value returned by `gradient` for it
+    fn descend<M, G>(&self, num_events: &mut usize, model: &mut M, gradient: G)
+    where
+        M: Model,
+        G: Fn(&M, usize) -> f64,
+    {
         let learning_rate = annealed_learning_rate(*num_events, self.l0, self.t);
 
         for ci in 0..model.num_coefficients() {
-            *model.coefficient(ci) = *model.coefficient(ci)
-                - learning_rate * gradient(cost, &prediction, truth, &model.gradient(ci, features));
+            *model.coefficient(ci) -= learning_rate * gradient(model, ci);
         }
         *num_events += 1;
     }
@@ -154,15 +239,35 @@ where
     ) where
         C: Cost<Y, M::Target>,
         Y: Copy,
+    {
+        let gradient = EventGradient::new(cost, model, features, truth);
+        self.descend(training, model, |model, ci| gradient.at(model, ci));
+    }
+}
+
+impl<M> GradientTeacher<M> for Momentum
+where
+    M: Model,
+    M::Target: Vector,
+{
+    fn teach_gradient(&self, training: &mut (usize, Vec<f64>), model: &mut M, gradient: &[f64]) {
+        self.descend(training, model, |_, ci| gradient[ci]);
+    }
+}
+
+impl Momentum {
+    /// Changes each coefficient of `model` by the value returned by `gradient` for it
+    fn descend<M, G>(&self, training: &mut (usize, Vec<f64>), model: &mut M, gradient: G)
+    where
+        M: Model,
+        G: Fn(&M, usize) -> f64,
     {
         let (ref mut num_events, ref mut velocity) = *training;
-        let prediction = model.predict(features);
         let learning_rate = annealed_learning_rate(*num_events, self.l0, self.t);
 
-        for ci in 0..model.num_coefficients() {
-            velocity[ci] = self.inertia * velocity[ci]
-                - learning_rate * gradient(cost, &prediction, truth, &model.gradient(ci, features));
-            *model.coefficient(ci) = *model.coefficient(ci) + velocity[ci];
+        for (ci, v) in velocity.iter_mut().enumerate() {
+            *v = self.inertia * *v - learning_rate * gradient(model, ci);
+            *model.coefficient(ci) += *v;
         }
         *num_events += 1;
     }
@@ -176,6 +281,11 @@ where
 /// Source:
 /// [G. Hinton's lecture 6c]
 /// (http://www.cs.toronto.edu/~tijmen/csc321/slides/lecture_slides_lec6.pdf)
+///
+/// Used as a `GradientTeacher`, e.g. by `GradientClipping`, the gradient handed to it has already
+/// been computed at the current position rather than at the estimated new one. The gradient then
+/// lacks the correction which distinguishes Nesterov from plain `Momentum`, and it behaves just
+/// like the latter.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Nesterov {
     /// Start learning rate
@@ -215,19 +325,49 @@ where
     ) where
         C: Cost<Y, M::Target>,
         Y: Copy,
+    {
+        // The cost is derived at the current position, the model at the estimated new one
+        let outer_derivative = cost.outer_derivative(&model.predict(features), truth);
+        Self::look_ahead(model, &training.1);
+        let gradient = EventGradient::with_outer_derivative(outer_derivative, model, features);
+        self.descend(training, model, |model, ci| gradient.at(model, ci));
+    }
+}
+
+impl<M> GradientTeacher<M> for Nesterov
+where
+    M: Model,
+    M::Target: Vector,
+{
+    /// `gradient` has been computed before the look ahead, so this behaves like plain `Momentum`
+    fn teach_gradient(&self, training: &mut (usize, Vec<f64>), model: &mut M, gradient: &[f64]) {
+        Self::look_ahead(model, &training.1);
+        self.descend(training, model, |_, ci| gradient[ci]);
+    }
+}
+
+impl Nesterov {
+    /// Moves the coefficients of `model` to the estimated new position
+    fn look_ahead<M: Model>(model: &mut M, velocity: &[f64]) {
+        for (ci, v) in velocity.iter().enumerate() {
+            *model.coefficient(ci) += v;
+        }
+    }
+
+    /// Changes each coefficient of `model` by the value returned by `gradient` for it, after
+    /// `look_ahead`
+    fn descend<M, G>(&self, training: &mut (usize, Vec<f64>), model: &mut M, gradient: G)
+    where
+        M: Model,
+        G: Fn(&M, usize) -> f64,
     {
         let (ref mut num_events, ref mut velocity) = *training;
-        let prediction = model.predict(features);
         let learning_rate = annealed_learning_rate(*num_events, self.l0, self.t);
 
-        for ci in 0..model.num_coefficients() {
-            *model.coefficient(ci) = *model.coefficient(ci) + velocity[ci];
-        }
-        for ci in 0..model.num_coefficients() {
-            let delta =
-                -learning_rate * gradient(cost, &prediction, truth, &model.gradient(ci, features));
-            *model.coefficient(ci) = *model.coefficient(ci) + delta;
-            velocity[ci] = self.inertia * velocity[ci] + delta;
+        for (ci, v) in velocity.iter_mut().enumerate() {
+            let delta = -learning_rate * gradient(model, ci);
+            *model.coefficient(ci) += delta;
+            *v = self.inertia * *v + delta;
         }
         *num_events += 1;
     }
@@ -271,12 +411,306 @@ where
         C: Cost<Y, M::Target>,
         Y: Copy,
     {
-        let prediction = model.predict(features);
-        for ci in 0..model.num_coefficients() {
-            let gradient = gradient(cost, &prediction, truth, &model.gradient(ci, features));
-            let delta = -self.learning_rate * gradient / squared_gradients[ci].sqrt();
+        let gradient = EventGradient::new(cost, model, features, truth);
+        self.descend(squared_gradients, model, |model, ci| gradient.at(model, ci));
+    }
+}
+
+impl<M> GradientTeacher<M> for Adagard
+where
+    M: Model,
+    M::Target: Vector,
+{
+    fn teach_gradient(&self, squared_gradients: &mut Vec<f64>, model: &mut M, gradient: &[f64]) {
+        self.descend(squared_gradients, model, |_, ci| gradient[ci]);
+    }
+}
+
+impl Adagard {
+    /// Changes each coefficient of `model` by the value returned by `gradient` for it
+    fn descend<M, G>(&self, squared_gradients: &mut [f64], model: &mut M, gradient: G)
+    where
+        M: Model,
+        G: Fn(&M, usize) -> f64,
+    {
+        for (ci, squared_gradient) in squared_gradients.iter_mut().enumerate() {
+            let gradient = gradient(model, ci);
+            let delta = -self.learning_rate * gradient / squared_gradient.sqrt();
             *model.coefficient(ci) += delta;
-            squared_gradients[ci] += gradient.powi(2);
+            *squared_gradient += gradient.powi(2);
         }
     }
 }
+
+/// Limit applied to the gradient by `GradientClipping`
+///
+/// The threshold must be finite and must not be negative. `GradientClipping` panics otherwise.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Clip {
+    /// Clamps each element of the gradient into the interval `[-threshold, threshold]`
+    Value(f64),
+    /// Scales the gradient down, so its euclidean norm does not exceed the threshold
+    Norm(f64),
+}
+
+impl Clip {
+    /// Panics if the threshold is negative or not finite
+    fn validate(self) {
+        let threshold = match self {
+            Clip::Value(threshold) | Clip::Norm(threshold) => threshold,
+        };
+        assert!(
+            threshold >= 0.0 && threshold.is_finite(),
+            "Clipping threshold must be finite and not negative, but is {}",
+            threshold
+        );
+    }
+
+    /// Applies the limit to `gradient`
+    fn apply(self, gradient: &mut [f64]) {
+        self.validate();
+        match self {
+            Clip::Value(threshold) => {
+                for g in gradient.iter_mut() {
+                    *g = g.max(-threshold).min(threshold);
+                }
+            }
+            Clip::Norm(threshold) => {
+                let norm = gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+                if norm > threshold {
+                    for g in gradient.iter_mut() {
+                        *g *= threshold / norm;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Clips the gradient before passing it on to another teacher
+///
+/// Events with extreme feature values may cause huge changes of the coefficients. This decorator
+/// limits the gradient of each event, before it is applied by the wrapped `teacher`.
+///
+/// # Example
+///
+/// ```
+/// # use vikos::{model, teacher, cost, learn_history};
+/// # let history = [(0.0, 3.0), (1.0, 4.0), (2.0, 5.0)];
+/// let mut model = model::Linear { m: 0.0, c: 0.0 };
+/// let teacher = teacher::GradientClipping {
+///     teacher: teacher::GradientDescent { learning_rate: 0.2 },
+///     clip: teacher::Clip::Norm(10.0),
+/// };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(&teacher, &cost, &mut model, history.iter().cloned());
+/// ```
+///
+/// # Panics
+///
+/// If the threshold of `clip` is negative or not finite.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GradientClipping<T> {
+    /// Teacher applying the clipped gradient to the model
+    pub teacher: T,
+    /// Limit applied to the gradient of each event
+    pub clip: Clip,
+}
+
+impl<M, T> Teacher<M> for GradientClipping<T>
+where
+    M: Model,
+    M::Target: Vector,
+    T: GradientTeacher<M>,
+{
+    type Training = T::Training;
+
+    fn new_training(&self, model: &M) -> T::Training {
+        self.clip.validate();
+        self.teacher.new_training(model)
+    }
+
+    fn teach_event<Y, C>(
+        &self,
+        training: &mut T::Training,
+        model: &mut M,
+        cost: &C,
+        features: &M::Features,
+        truth: Y,
+    ) where
+        C: Cost<Y, M::Target>,
+        Y: Copy,
+    {
+        let event = EventGradient::new(cost, model, features, truth);
+        let gradient: Vec<_> = (0..model.num_coefficients())
+            .map(|ci| event.at(model, ci))
+            .collect();
+        self.teach_gradient(training, model, &gradient);
+    }
+}
+
+impl<M, T> GradientTeacher<M> for GradientClipping<T>
+where
+    M: Model,
+    M::Target: Vector,
+    T: GradientTeacher<M>,
+{
+    fn teach_gradient(&self, training: &mut T::Training, model: &mut M, gradient: &[f64]) {
+        let mut gradient = gradient.to_vec();
+        self.clip.apply(&mut gradient);
+        self.teacher.teach_gradient(training, model, &gradient);
+    }
+}
+
+/// How `Averaged` weighs the coefficients after each event
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Average {
+    /// Mean of the coefficients after every event so far (Polyak-Ruppert averaging)
+    Polyak,
+    /// Exponential moving average with the given decay, e.g. `0.99`
+    ///
+    /// After each event the average moves towards the current coefficients by `1 - decay`. The
+    /// decay must be within `[0, 1]`. `Averaged` panics otherwise.
+    Exponential(f64),
+}
+
+impl Average {
+    /// Panics if the decay is outside of `[0, 1]`
+    fn validate(self) {
+        if let Average::Exponential(decay) = self {
+            assert!(
+                (0.0..=1.0).contains(&decay),
+                "Decay of exponential average must be within [0, 1], but is {}",
+                decay
+            );
+        }
+    }
+}
+
+/// Maintains a running average of the coefficients, while another teacher trains the model
+///
+/// The coefficients of stochastic gradient descent jitter from event to event. Their average often
+/// generalizes better than the latest values. The model itself is trained by the wrapped `teacher`
+/// as usual, the average is kept in the `AveragedTraining`.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, model, teacher, Model, Teacher};
+///
+/// let history = [(0.0, 2.8), (1.0, 4.1), (2.0, 5.2), (3.0, 5.9)];
+/// let mut model = model::Linear { m: 0.0, c: 0.0 };
+/// let teacher = teacher::Averaged {
+///     teacher: teacher::GradientDescent { learning_rate: 0.1 },
+///     average: teacher::Average::Polyak,
+/// };
+/// let cost = cost::LeastSquares {};
+///
+/// let mut training = teacher.new_training(&model);
+/// for &(features, truth) in history.iter().cycle().take(400) {
+///     teacher.teach_event(&mut training, &mut model, &cost, &features, truth);
+/// }
+/// let averaged = training.averaged_model(&model);
+/// println!("{}", averaged.predict(&4.0));
+/// ```
+///
+/// # Panics
+///
+/// If the decay of an `Average::Exponential` is outside of `[0, 1]`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Averaged<T> {
+    /// Teacher training the model
+    pub teacher: T,
+    /// Kind of average maintained
+    pub average: Average,
+}
+
+/// `Teacher::Training` of `Averaged`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AveragedTraining<T> {
+    /// Training of the wrapped teacher
+    pub training: T,
+    /// Number of events averaged so far
+    pub events: usize,
+    /// Averaged coefficients. Empty until the first event has been taught.
+    pub coefficients: Vec<f64>,
+}
+
+impl<T> AveragedTraining<T> {
+    /// Copy of `model` with the averaged coefficients
+    ///
+    /// The copy can be used for prediction or serialized like any other model. Returns an
+    /// unchanged copy if no event has been taught yet.
+    pub fn averaged_model<M: Model + Clone>(&self, model: &M) -> M {
+        let mut averaged = model.clone();
+        for (ci, &coefficient) in self.coefficients.iter().enumerate() {
+            *averaged.coefficient(ci) = coefficient;
+        }
+        averaged
+    }
+
+    /// Includes the current coefficients of `model` in the average
+    fn update<M: Model>(&mut self, model: &mut M, average: Average) {
+        average.validate();
+        self.events += 1;
+        if self.coefficients.is_empty() {
+            self.coefficients = (0..model.num_coefficients())
+                .map(|ci| *model.coefficient(ci))
+                .collect();
+            return;
+        }
+        let rate = match average {
+            Average::Polyak => 1.0 / self.events as f64,
+            Average::Exponential(decay) => 1.0 - decay,
+        };
+        for (ci, mean) in self.coefficients.iter_mut().enumerate() {
+            *mean += rate * (*model.coefficient(ci) - *mean);
+        }
+    }
+}
+
+impl<M, T> Teacher<M> for Averaged<T>
+where
+    M: Model,
+    T: Teacher<M>,
+{
+    type Training = AveragedTraining<T::Training>;
+
+    fn new_training(&self, model: &M) -> Self::Training {
+        self.average.validate();
+        AveragedTraining {
+            training: self.teacher.new_training(model),
+            events: 0,
+            coefficients: Vec::new(),
+        }
+    }
+
+    fn teach_event<Y, C>(
+        &self,
+        training: &mut Self::Training,
+        model: &mut M,
+        cost: &C,
+        features: &M::Features,
+        truth: Y,
+    ) where
+        C: Cost<Y, M::Target>,
+        Y: Copy,
+    {
+        self.teacher
+            .teach_event(&mut training.training, model, cost, features, truth);
+        training.update(model, self.average);
+    }
+}
+
+impl<M, T> GradientTeacher<M> for Averaged<T>
+where
+    M: Model,
+    T: GradientTeacher<M>,
+{
+    fn teach_gradient(&self, training: &mut Self::Training, model: &mut M, gradient: &[f64]) {
+        self.teacher
+            .teach_gradient(&mut training.training, model, gradient);
+        training.update(model, self.average);
+    }
+}
@@ -0,0 +1,1239 @@
+//! Transformations turning raw input into features for a `Model`
+
+use crate::linear_algebra::Vector;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+    f64::consts::PI,
+    marker::PhantomData,
+};
+
+/// Maps raw input to the features of a `Model`
+pub trait Transformer {
+    /// Raw input which is transformed
+    type Input;
+    /// Features created from the input
+    type Output;
+
+    /// Creates features from `input`
+    fn transform(&self, input: &Self::Input) -> Self::Output;
+}
+
+/// Applies the first transformer, then the second one to its output
+impl<A, B> Transformer for (A, B)
+where
+    A: Transformer,
+    B: Transformer<Input = A::Output>,
+{
+    type Input = A::Input;
+    type Output = B::Output;
+
+    fn transform(&self, input: &A::Input) -> B::Output {
+        self.1.transform(&self.0.transform(input))
+    }
+}
+
+/// Expands features into all products of up to `degree` features
+///
+/// The output is ordered by degree first. E.g. for two features and degree two: `x0`, `x1`,
+/// `x0*x0`, `x0*x1`, `x1*x1`.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::{PolynomialFeatures, Transformer};
+///
+/// let expansion = PolynomialFeatures::interaction_only(3, 2);
+/// assert_eq!(vec![2.0, 3.0, 5.0, 6.0, 10.0, 15.0], expansion.transform(&[2.0, 3.0, 5.0]));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolynomialFeatures<V> {
+    /// Each term is the product of the features at the listed indices
+    terms: Vec<Vec<usize>>,
+    input: PhantomData<V>,
+}
+
+impl<V> PolynomialFeatures<V> {
+    /// Expands features of the specified dimension into all products of up to `degree` features,
+    /// including powers of single features
+    pub fn new(dimension: usize, degree: usize) -> Self {
+        Self::from_terms(dimension, degree, false)
+    }
+
+    /// Expands features of the specified dimension into all products of up to `degree` distinct
+    /// features, e.g. `x0 * x1`, but not `x0 * x0`
+    pub fn interaction_only(dimension: usize, degree: usize) -> Self {
+        Self::from_terms(dimension, degree, true)
+    }
+
+    fn from_terms(dimension: usize, degree: usize, interaction_only: bool) -> Self {
+        // Terms of degree n are created by appending an index to each term of degree n - 1. To
+        // list each product only once the indices within a term never decrease.
+        let mut terms = Vec::new();
+        let mut previous: Vec<Vec<usize>> = vec![Vec::new()];
+        for _ in 0..degree {
+            let mut current = Vec::new();
+            for term in &previous {
+                let start = match term.last() {
+                    None => 0,
+                    Some(&last) if interaction_only => last + 1,
+                    Some(&last) => last,
+                };
+                for index in start..dimension {
+                    let mut next = term.clone();
+                    next.push(index);
+                    current.push(next);
+                }
+            }
+            terms.extend(current.iter().cloned());
+            previous = current;
+        }
+        PolynomialFeatures {
+            terms,
+            input: PhantomData,
+        }
+    }
+
+    /// Number of terms the features are expanded into
+    pub fn output_dimension(&self) -> usize {
+        self.terms.len()
+    }
+}
+
+impl<V> Transformer for PolynomialFeatures<V>
+where
+    V: Vector,
+{
+    type Input = V;
+    type Output = Vec<f64>;
+
+    fn transform(&self, input: &V) -> Vec<f64> {
+        self.terms
+            .iter()
+            .map(|term| term.iter().map(|&i| input.at(i)).product())
+            .collect()
+    }
+}
+
+/// Maps a variable number of tokens into a vector of fixed dimension using the hashing trick
+///
+/// Each token is hashed to an index, at which `1` or `-1` (also decided by the hash) is added.
+/// Useful for categorical features with a large or unknown number of categories, at the risk of
+/// collisions between tokens. The hash function is stable across platforms and releases, so
+/// persisted models stay valid.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::{FeatureHasher, Transformer};
+///
+/// let hasher = FeatureHasher::new(16);
+/// let features = hasher.transform(&vec!["store=berlin", "product=milk"]);
+/// assert_eq!(16, features.len());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureHasher<S> {
+    /// Dimension of the output
+    dimension: usize,
+    input: PhantomData<S>,
+}
+
+impl<S> FeatureHasher<S> {
+    /// Creates a hasher mapping tokens into `dimension` features
+    ///
+    /// # Panics
+    ///
+    /// If `dimension` is zero.
+    pub fn new(dimension: usize) -> Self {
+        assert!(dimension > 0, "Feature hasher needs at least one bucket");
+        FeatureHasher {
+            dimension,
+            input: PhantomData,
+        }
+    }
+}
+
+impl<S> Transformer for FeatureHasher<S>
+where
+    S: AsRef<str>,
+{
+    type Input = Vec<S>;
+    type Output = Vec<f64>;
+
+    fn transform(&self, input: &Vec<S>) -> Vec<f64> {
+        let mut output = vec![0.0; self.dimension];
+        for token in input {
+            let hash = fnv1a(token.as_ref().as_bytes());
+            // Use the highest bit for the sign, so it is independent of the index
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            output[(hash % self.dimension as u64) as usize] += sign;
+        }
+        output
+    }
+}
+
+/// 64 bit FNV-1a hash
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Treatment of categories an encoder has not seen during fitting
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Unknown {
+    /// Unknown categories are encoded with all features of their column set to zero
+    Ignore,
+    /// Each column gets an additional feature, which is set for unknown categories
+    Bucket,
+}
+
+/// Encodes columns of categories as one hot vectors
+///
+/// Each column is mapped to one feature per category seen during `fit`, ordered alphabetically.
+/// The feature of the category present is set to `1`, all others are `0`. The features of all
+/// columns are concatenated.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::{OneHotEncoder, Transformer, Unknown};
+///
+/// let rows = [["berlin", "milk"], ["hamburg", "milk"], ["berlin", "bread"]];
+/// let encoder = OneHotEncoder::fit(2, Unknown::Ignore, rows.iter().map(|row| row.to_vec()));
+/// assert_eq!(
+///     vec![0.0, 1.0, 0.0, 1.0],
+///     encoder.transform(&vec!["hamburg", "milk"])
+/// );
+/// assert_eq!(
+///     vec![0.0, 0.0, 1.0, 0.0],
+///     encoder.transform(&vec!["munich", "bread"])
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneHotEncoder<S> {
+    /// Index of each category within its column
+    vocabularies: Vec<BTreeMap<String, usize>>,
+    /// Treatment of unknown categories
+    unknown: Unknown,
+    input: PhantomData<S>,
+}
+
+fn check_columns<S>(num_columns: usize, row: &[S]) {
+    assert_eq!(
+        num_columns,
+        row.len(),
+        "Number of categories does not match the columns of the encoder"
+    );
+}
+
+impl<S> OneHotEncoder<S>
+where
+    S: AsRef<str>,
+{
+    /// Learns the categories of `num_columns` columns from `rows`
+    ///
+    /// # Panics
+    ///
+    /// If a row does not have `num_columns` columns.
+    pub fn fit<I>(num_columns: usize, unknown: Unknown, rows: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Vec<S>>,
+    {
+        let mut categories = vec![BTreeSet::new(); num_columns];
+        for row in rows {
+            check_columns(num_columns, row.borrow());
+            for (column, category) in categories.iter_mut().zip(row.borrow()) {
+                column.insert(category.as_ref().to_string());
+            }
+        }
+        OneHotEncoder {
+            vocabularies: categories
+                .into_iter()
+                .map(|column| {
+                    column
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, c)| (c, i))
+                        .collect()
+                })
+                .collect(),
+            unknown,
+            input: PhantomData,
+        }
+    }
+
+    /// Number of features of the encoded columns
+    pub fn output_dimension(&self) -> usize {
+        (0..self.vocabularies.len())
+            .map(|column| self.column_dimension(column))
+            .sum()
+    }
+
+    fn column_dimension(&self, column: usize) -> usize {
+        match self.unknown {
+            Unknown::Ignore => self.vocabularies[column].len(),
+            Unknown::Bucket => self.vocabularies[column].len() + 1,
+        }
+    }
+}
+
+impl<S> Transformer for OneHotEncoder<S>
+where
+    S: AsRef<str>,
+{
+    type Input = Vec<S>;
+    type Output = Vec<f64>;
+
+    fn transform(&self, input: &Vec<S>) -> Vec<f64> {
+        check_columns(self.vocabularies.len(), input);
+        let mut output = vec![0.0; self.output_dimension()];
+        let mut offset = 0;
+        for (column, category) in input.iter().enumerate() {
+            let vocabulary = &self.vocabularies[column];
+            match (vocabulary.get(category.as_ref()), self.unknown) {
+                (Some(&index), _) => output[offset + index] = 1.0,
+                (None, Unknown::Bucket) => output[offset + vocabulary.len()] = 1.0,
+                (None, Unknown::Ignore) => (),
+            }
+            offset += self.column_dimension(column);
+        }
+        output
+    }
+}
+
+/// Maps class labels to the class indices used as truth for multi classification
+///
+/// Classes are numbered in alphabetical order of their labels.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::LabelEncoder;
+///
+/// let labels = LabelEncoder::fit(vec!["virginica", "setosa", "versicolor", "setosa"]);
+/// assert_eq!(3, labels.num_classes());
+/// assert_eq!(Some(1), labels.encode("versicolor"));
+/// assert_eq!(None, labels.encode("unknown"));
+/// assert_eq!(Some("virginica"), labels.decode(2));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelEncoder {
+    /// Label of each class, ordered by class index
+    labels: Vec<String>,
+}
+
+impl LabelEncoder {
+    /// Learns the class labels occurring in `labels`
+    pub fn fit<I>(labels: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let labels: BTreeSet<_> = labels
+            .into_iter()
+            .map(|label| label.as_ref().to_string())
+            .collect();
+        LabelEncoder {
+            labels: labels.into_iter().collect(),
+        }
+    }
+
+    /// Number of distinct classes
+    pub fn num_classes(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Index of the class with `label`, or `None` if the label has not been seen during `fit`
+    pub fn encode(&self, label: &str) -> Option<usize> {
+        self.labels
+            .binary_search_by(|probe| probe.as_str().cmp(label))
+            .ok()
+    }
+
+    /// Label of the class with `index`
+    pub fn decode(&self, index: usize) -> Option<&str> {
+        self.labels.get(index).map(String::as_str)
+    }
+}
+
+/// Value used by `Imputer` to replace missing features
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    /// Mean of the values present in the column
+    Mean,
+    /// Median of the values present in the column, estimated if updated online
+    Median,
+    /// A fixed value
+    Constant(f64),
+}
+
+/// Replaces missing features
+///
+/// Transforms rows with optional values into feature vectors, by filling in missing values
+/// according to a `Strategy`. Optionally an indicator feature is appended for each column, which is
+/// `1` if the value has been missing and `0` otherwise. This allows models to learn from the fact
+/// that a value is missing.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::{Imputer, Strategy, Transformer};
+///
+/// let rows = vec![vec![Some(1.0), None], vec![Some(3.0), Some(4.0)]];
+/// let imputer = Imputer::fit(2, Strategy::Mean, true, &rows);
+/// assert_eq!(
+///     vec![2.0, 4.0, 1.0, 0.0],
+///     imputer.transform(&vec![None, Some(4.0)])
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Imputer {
+    strategy: Strategy,
+    /// Whether to append indicators for missing values
+    indicators: bool,
+    /// Replacement for missing values of each column
+    fill: Vec<f64>,
+    /// Number of values present in each column, seen so far
+    counts: Vec<usize>,
+    /// Online estimates of the median of each column. Empty unless `Strategy::Median` is used.
+    medians: Vec<OnlineMedian>,
+}
+
+impl Imputer {
+    /// Creates an imputer which has not seen any values yet
+    ///
+    /// Until values are seen, missing values are replaced with zero, unless a
+    /// `Strategy::Constant` is used. If `indicators` is `true` an indicator feature is appended
+    /// for each of the `dimension` columns.
+    pub fn new(dimension: usize, strategy: Strategy, indicators: bool) -> Self {
+        let fill = match strategy {
+            Strategy::Constant(value) => value,
+            Strategy::Mean | Strategy::Median => 0.0,
+        };
+        let medians = match strategy {
+            Strategy::Median => vec![OnlineMedian::default(); dimension],
+            Strategy::Mean | Strategy::Constant(_) => Vec::new(),
+        };
+        Imputer {
+            strategy,
+            indicators,
+            fill: vec![fill; dimension],
+            counts: vec![0; dimension],
+            medians,
+        }
+    }
+
+    /// Determines the replacement for missing values of each column from `rows`
+    ///
+    /// The median of each column is computed exactly. It can still be refined with `update`
+    /// afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If a row does not have `dimension` columns.
+    pub fn fit<I>(dimension: usize, strategy: Strategy, indicators: bool, rows: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Vec<Option<f64>>>,
+    {
+        let mut imputer = Self::new(dimension, strategy, indicators);
+        match strategy {
+            Strategy::Median => {
+                let mut columns: Vec<Vec<f64>> = vec![Vec::new(); dimension];
+                for row in rows {
+                    let row = row.borrow();
+                    imputer.check_dimension(row);
+                    for (column, value) in columns.iter_mut().zip(row) {
+                        column.extend(value);
+                    }
+                }
+                for (i, mut column) in columns.into_iter().enumerate() {
+                    column.sort_by(|a, b| a.partial_cmp(b).expect("Features must not be NaN"));
+                    imputer.counts[i] = column.len();
+                    if !column.is_empty() {
+                        imputer.fill[i] = quantile(&column, 0.5);
+                    }
+                    imputer.medians[i] = OnlineMedian::from_sorted(&column);
+                }
+            }
+            Strategy::Mean | Strategy::Constant(_) => {
+                for row in rows {
+                    imputer.update(row.borrow());
+                }
+            }
+        }
+        imputer
+    }
+
+    /// Updates the replacements with the values present in `row`
+    ///
+    /// The mean is updated exactly. The median is estimated with the P² algorithm of Jain and
+    /// Chlamtac, which only keeps five values for each column. It is exact for up to five values.
+    ///
+    /// # Panics
+    ///
+    /// If `row` does not have one value for each column.
+    pub fn update(&mut self, row: &[Option<f64>]) {
+        self.check_dimension(row);
+        for (i, value) in row.iter().enumerate() {
+            if let Some(x) = *value {
+                self.counts[i] += 1;
+                match self.strategy {
+                    Strategy::Mean => {
+                        self.fill[i] += (x - self.fill[i]) / self.counts[i] as f64;
+                    }
+                    Strategy::Median => {
+                        self.medians[i].update(x);
+                        self.fill[i] = self.medians[i].estimate();
+                    }
+                    Strategy::Constant(_) => (),
+                }
+            }
+        }
+    }
+
+    fn check_dimension(&self, row: &[Option<f64>]) {
+        assert_eq!(
+            self.fill.len(),
+            row.len(),
+            "Number of values does not match the columns of the imputer"
+        );
+    }
+
+    /// Value used to replace missing values of each column
+    pub fn fill_values(&self) -> &[f64] {
+        &self.fill
+    }
+
+    /// Number of features created from each row
+    pub fn output_dimension(&self) -> usize {
+        if self.indicators {
+            2 * self.fill.len()
+        } else {
+            self.fill.len()
+        }
+    }
+}
+
+impl Transformer for Imputer {
+    type Input = Vec<Option<f64>>;
+    type Output = Vec<f64>;
+
+    fn transform(&self, input: &Vec<Option<f64>>) -> Vec<f64> {
+        self.check_dimension(input);
+        let mut output: Vec<_> = input
+            .iter()
+            .zip(&self.fill)
+            .map(|(value, fill)| value.unwrap_or(*fill))
+            .collect();
+        if self.indicators {
+            output.extend(
+                input
+                    .iter()
+                    .map(|value| if value.is_none() { 1.0 } else { 0.0 }),
+            );
+        }
+        output
+    }
+}
+
+/// Online estimate of the median using the P² algorithm
+///
+/// Keeps five markers: minimum, the 25%, 50% and 75% quantiles and maximum. Their heights are
+/// adjusted with a piecewise parabolic interpolation as values arrive.
+/// See [this paper](https://doi.org/10.1145/4372.4378) for more information.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct OnlineMedian {
+    /// Heights of the markers. Until five values have been seen, all values seen so far, sorted.
+    heights: Vec<f64>,
+    /// Actual positions of the markers, starting at one
+    positions: [f64; 5],
+    /// Desired positions of the markers
+    desired: [f64; 5],
+}
+
+impl OnlineMedian {
+    /// Desired quantile of each marker
+    const QUANTILES: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+    /// Estimator which has already seen the `sorted` values
+    fn from_sorted(sorted: &[f64]) -> Self {
+        // Fewer than five values are kept as they are, until the markers can be placed
+        if sorted.len() < 5 {
+            return OnlineMedian {
+                heights: sorted.to_vec(),
+                ..OnlineMedian::default()
+            };
+        }
+        let last = (sorted.len() - 1) as f64;
+        let mut median = OnlineMedian::default();
+        for (i, q) in Self::QUANTILES.iter().enumerate() {
+            median.desired[i] = 1.0 + q * last;
+            median.positions[i] = median.desired[i].round();
+            median
+                .heights
+                .push(sorted[median.positions[i] as usize - 1]);
+        }
+        median
+    }
+
+    fn update(&mut self, x: f64) {
+        if self.positions[4] == 0.0 {
+            // Still collecting the first five values
+            let index = self.heights.partition_point(|&h| h < x);
+            self.heights.insert(index, x);
+            if self.heights.len() == 5 {
+                self.positions = [1.0, 2.0, 3.0, 4.0, 5.0];
+                for (desired, q) in self.desired.iter_mut().zip(&Self::QUANTILES) {
+                    *desired = 1.0 + 4.0 * q;
+                }
+            }
+            return;
+        }
+
+        let q = &mut self.heights;
+        // Cell of the new value
+        let k = if x < q[0] {
+            q[0] = x;
+            0
+        } else if x >= q[4] {
+            q[4] = x;
+            3
+        } else {
+            (1..5).find(|&i| x < q[i]).unwrap() - 1
+        };
+        for position in &mut self.positions[k + 1..] {
+            *position += 1.0;
+        }
+        for (desired, quantile) in self.desired.iter_mut().zip(&Self::QUANTILES) {
+            *desired += quantile;
+        }
+
+        // Move the inner markers towards their desired positions
+        let n = &mut self.positions;
+        for i in 1..4 {
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                q[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                n[i] += d;
+            }
+        }
+    }
+
+    fn estimate(&self) -> f64 {
+        if self.positions[4] == 0.0 {
+            quantile(&self.heights, 0.5)
+        } else {
+            self.heights[2]
+        }
+    }
+}
+
+/// Approximates a radial basis function (RBF) kernel with random Fourier features
+///
+/// Maps the input to `z(x) = sqrt(2/D) * cos(W * x + b)`, so that the dot product `z(x) * z(y)`
+/// approximates the kernel `e^(-gamma * |x - y|^2)`. The rows of `W` are drawn from a normal
+/// distribution with variance `2 * gamma` and `b` uniformly from `[0, 2 pi)`. Training a `Linear`
+/// or `Logistic` model on the transformed features approximates kernel ridge regression or kernel
+/// logistic regression respectively. The larger the output dimension `D` the better the
+/// approximation.
+/// See [this paper](https://people.eecs.berkeley.edu/~brecht/papers/07.rah.rec.nips.pdf) for more
+/// information.
+///
+/// # Example
+///
+/// ```
+/// use vikos::{cost, learn_history, model, teacher};
+/// use vikos::transform::{RandomFourierFeatures, Transformer};
+///
+/// let history = [([0.0], 0.0), ([1.5], 1.0), ([3.0], 0.1)];
+/// let rff = RandomFourierFeatures::new(1, 100, 0.5, 42);
+/// let mut model = model::Linear::with_feature_dimension(100);
+/// let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+/// let cost = cost::LeastSquares {};
+///
+/// learn_history(
+///     &teacher,
+///     &cost,
+///     &mut model,
+///     history.iter().map(|(x, y)| (rff.transform(x), *y)),
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomFourierFeatures<V> {
+    /// Weight of input `i` for output `j` is stored at `j * input_dimension + i`
+    weights: Vec<f64>,
+    /// Phase offset of each output
+    offsets: Vec<f64>,
+    input: PhantomData<V>,
+}
+
+impl<V> RandomFourierFeatures<V> {
+    /// Draws a new random mapping from `dimension` inputs to `output_dimension` features
+    ///
+    /// `gamma` is the parameter of the approximated kernel `e^(-gamma * |x - y|^2)`. The random
+    /// generator used is seeded with `seed`.
+    pub fn new(dimension: usize, output_dimension: usize, gamma: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let deviation = (2.0 * gamma).sqrt();
+        RandomFourierFeatures {
+            weights: (0..dimension * output_dimension)
+                .map(|_| deviation * standard_normal(&mut rng))
+                .collect(),
+            offsets: (0..output_dimension)
+                .map(|_| rng.gen_range(0.0..2.0 * PI))
+                .collect(),
+            input: PhantomData,
+        }
+    }
+
+    /// Dimension of the transformed features
+    pub fn output_dimension(&self) -> usize {
+        self.offsets.len()
+    }
+}
+
+impl<V> Transformer for RandomFourierFeatures<V>
+where
+    V: Vector,
+{
+    type Input = V;
+    type Output = Vec<f64>;
+
+    fn transform(&self, input: &V) -> Vec<f64> {
+        let dimension = input.dimension();
+        assert_eq!(
+            self.weights.len(),
+            dimension * self.output_dimension(),
+            "Dimension of input does not match the random Fourier features"
+        );
+        let scale = (2.0 / self.output_dimension() as f64).sqrt();
+        self.offsets
+            .iter()
+            .enumerate()
+            .map(|(j, offset)| {
+                let row = &self.weights[j * dimension..(j + 1) * dimension];
+                let projection: f64 = row.iter().enumerate().map(|(i, w)| w * input.at(i)).sum();
+                scale * (projection + offset).cos()
+            })
+            .collect()
+    }
+}
+
+/// Scales each feature to zero mean and unit variance
+///
+/// Mean and variance can be estimated from a history using `fit`, or be updated online with each
+/// event using `update`. Features with zero variance are only shifted.
+///
+/// # Example
+///
+/// ```
+/// use vikos::transform::{StandardScaler, Transformer};
+///
+/// let history = [([1.0, 200.0], true), ([3.0, 400.0], false)];
+/// let scaler = StandardScaler::fit(2, history.iter().map(|(x, _)| x));
+/// assert_eq!([-1.0, -1.0], scaler.transform(&[1.0, 200.0]));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandardScaler<V> {
+    /// Number of events seen so far
+    count: usize,
+    /// Running mean of each feature
+    mean: Vec<f64>,
+    /// Running sum of squared deviations from the mean of each feature
+    squared_deviations: Vec<f64>,
+    input: PhantomData<V>,
+}
+
+impl<V: Vector> StandardScaler<V> {
+    /// Creates a scaler which has not seen any events yet and therefore does not change features
+    pub fn new(dimension: usize) -> Self {
+        StandardScaler {
+            count: 0,
+            mean: vec![0.0; dimension],
+            squared_deviations: vec![0.0; dimension],
+            input: PhantomData,
+        }
+    }
+
+    /// Estimates mean and variance of the features in `inputs`
+    pub fn fit<I>(dimension: usize, inputs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<V>,
+    {
+        let mut scaler = Self::new(dimension);
+        for input in inputs {
+            scaler.update(input.borrow());
+        }
+        scaler
+    }
+
+    /// Updates mean and variance with a single event
+    pub fn update(&mut self, input: &V) {
+        // Welford's online algorithm
+        self.count += 1;
+        for i in 0..self.mean.len() {
+            let x = input.at(i);
+            let delta = x - self.mean[i];
+            self.mean[i] += delta / self.count as f64;
+            self.squared_deviations[i] += delta * (x - self.mean[i]);
+        }
+    }
+
+    /// Estimated mean of each feature
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    /// Estimated (population) variance of each feature
+    pub fn variance(&self) -> Vec<f64> {
+        self.squared_deviations
+            .iter()
+            .map(|s| {
+                if self.count == 0 {
+                    0.0
+                } else {
+                    s / self.count as f64
+                }
+            })
+            .collect()
+    }
+}
+
+impl<V> Transformer for StandardScaler<V>
+where
+    V: Vector,
+{
+    type Input = V;
+    type Output = V;
+
+    fn transform(&self, input: &V) -> V {
+        let mut output = input.clone();
+        for (i, (mean, squared_deviation)) in
+            self.mean.iter().zip(&self.squared_deviations).enumerate()
+        {
+            // Same as `variance`, without allocating a vector
+            let deviation = if *squared_deviation > 0.0 {
+                (squared_deviation / self.count as f64).sqrt()
+            } else {
+                1.0
+            };
+            *output.at_mut(i) = (input.at(i) - mean) / deviation;
+        }
+        output
+    }
+}
+
+/// Scales each feature into the interval `[0, 1]`
+///
+/// Minimum and maximum can be determined from a history using `fit`, or be updated online with
+/// each event using `update`. Features which have always had the same value are mapped to `0`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinMaxScaler<V> {
+    /// Smallest value seen for each feature
+    min: Vec<f64>,
+    /// Largest value seen for each feature
+    max: Vec<f64>,
+    input: PhantomData<V>,
+}
+
+impl<V: Vector> MinMaxScaler<V> {
+    /// Creates a scaler which has not seen any events yet
+    pub fn new(dimension: usize) -> Self {
+        MinMaxScaler {
+            min: vec![f64::INFINITY; dimension],
+            max: vec![f64::NEG_INFINITY; dimension],
+            input: PhantomData,
+        }
+    }
+
+    /// Determines the range of each feature in `inputs`
+    pub fn fit<I>(dimension: usize, inputs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<V>,
+    {
+        let mut scaler = Self::new(dimension);
+        for input in inputs {
+            scaler.update(input.borrow());
+        }
+        scaler
+    }
+
+    /// Extends the range of each feature, so it includes `input`
+    pub fn update(&mut self, input: &V) {
+        for i in 0..self.min.len() {
+            self.min[i] = self.min[i].min(input.at(i));
+            self.max[i] = self.max[i].max(input.at(i));
+        }
+    }
+}
+
+impl<V> Transformer for MinMaxScaler<V>
+where
+    V: Vector,
+{
+    type Input = V;
+    type Output = V;
+
+    fn transform(&self, input: &V) -> V {
+        let mut output = input.clone();
+        for i in 0..self.min.len() {
+            let range = self.max[i] - self.min[i];
+            *output.at_mut(i) = if range > 0.0 {
+                (input.at(i) - self.min[i]) / range
+            } else {
+                0.0
+            };
+        }
+        output
+    }
+}
+
+/// Scales each feature using statistics which are robust to outliers
+///
+/// Subtracts the median and divides by the interquartile range (the difference between the 75th
+/// and the 25th percentile). Since quantiles can not be updated online exactly, this scaler can
+/// only be fit on a history. Features with an interquartile range of zero are only shifted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RobustScaler<V> {
+    /// Median of each feature
+    median: Vec<f64>,
+    /// Interquartile range of each feature
+    iqr: Vec<f64>,
+    input: PhantomData<V>,
+}
+
+impl<V: Vector> RobustScaler<V> {
+    /// Determines median and interquartile range of each feature in `inputs`
+    pub fn fit<I>(dimension: usize, inputs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<V>,
+    {
+        let mut columns = vec![Vec::new(); dimension];
+        for input in inputs {
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push(input.borrow().at(i));
+            }
+        }
+        let mut median = Vec::with_capacity(dimension);
+        let mut iqr = Vec::with_capacity(dimension);
+        for mut column in columns {
+            column.sort_by(|a, b| a.partial_cmp(b).expect("Features must not be NaN"));
+            median.push(quantile(&column, 0.5));
+            iqr.push(quantile(&column, 0.75) - quantile(&column, 0.25));
+        }
+        RobustScaler {
+            median,
+            iqr,
+            input: PhantomData,
+        }
+    }
+}
+
+impl<V> Transformer for RobustScaler<V>
+where
+    V: Vector,
+{
+    type Input = V;
+    type Output = V;
+
+    fn transform(&self, input: &V) -> V {
+        let mut output = input.clone();
+        for i in 0..self.median.len() {
+            let scale = if self.iqr[i] > 0.0 { self.iqr[i] } else { 1.0 };
+            *output.at_mut(i) = (input.at(i) - self.median[i]) / scale;
+        }
+        output
+    }
+}
+
+/// `q`-quantile of `sorted` values, interpolating linearly between neighbouring values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Draws a sample from the standard normal distribution using the Box-Muller transform
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>(); // in (0, 1], so the logarithm is finite
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        fnv1a, quantile, FeatureHasher, Imputer, LabelEncoder, MinMaxScaler, OneHotEncoder,
+        PolynomialFeatures, RandomFourierFeatures, RobustScaler, StandardScaler, Strategy,
+        Transformer, Unknown,
+    };
+    use crate::linear_algebra::Vector;
+
+    #[test]
+    fn approximates_rbf_kernel() {
+        let gamma = 0.5;
+        let rff = RandomFourierFeatures::new(2, 5000, gamma, 42);
+        let x: [f64; 2] = [0.3, -0.2];
+        for y in &[[0.3, -0.2], [0.8, 0.1], [-0.5, 1.0], [2.0, 2.0]] {
+            let distance: f64 = (0..2).map(|i| (x[i] - y[i]).powi(2)).sum();
+            let kernel = (-gamma * distance).exp();
+            let approximation = rff.transform(&x).dot(&rff.transform(y));
+            println!("kernel: {}, approximation: {}", kernel, approximation);
+            assert!((kernel - approximation).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_features() {
+        let a = RandomFourierFeatures::new(2, 10, 1.0, 7);
+        let b = RandomFourierFeatures::new(2, 10, 1.0, 7);
+        assert_eq!(a.transform(&vec![1.0, 2.0]), b.transform(&vec![1.0, 2.0]));
+        assert_eq!(10, a.output_dimension());
+    }
+
+    #[test]
+    #[should_panic(expected = "Feature hasher needs at least one bucket")]
+    fn feature_hasher_without_buckets() {
+        FeatureHasher::<&str>::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dimension of input does not match the random Fourier features")]
+    fn random_fourier_features_wrong_dimension() {
+        let rff = RandomFourierFeatures::new(2, 10, 1.0, 7);
+        rff.transform(&vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn standard_scaler() {
+        let inputs = [[1.0, 5.0], [2.0, 5.0], [3.0, 5.0], [6.0, 5.0]];
+        let scaler = StandardScaler::fit(2, inputs.iter());
+        assert_eq!(&[3.0, 5.0], scaler.mean());
+        assert_eq!(vec![3.5, 0.0], scaler.variance());
+        let scaled = scaler.transform(&[6.0, 7.0]);
+        assert!((scaled[0] - 3.0 / 3.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(2.0, scaled[1]);
+    }
+
+    #[test]
+    fn standard_scaler_online() {
+        let inputs = vec![vec![1.0], vec![2.0], vec![3.0], vec![6.0]];
+        let mut scaler = StandardScaler::new(1);
+        assert_eq!(vec![4.0], scaler.transform(&vec![4.0]));
+        for input in &inputs {
+            scaler.update(input);
+        }
+        let fitted = StandardScaler::fit(1, inputs);
+        assert_eq!(fitted.transform(&vec![4.0]), scaler.transform(&vec![4.0]));
+    }
+
+    #[test]
+    fn min_max_scaler() {
+        let inputs = [[1.0, 5.0], [3.0, 5.0], [2.0, 5.0]];
+        let scaler = MinMaxScaler::fit(2, inputs.iter());
+        assert_eq!([0.5, 0.0], scaler.transform(&[2.0, 5.0]));
+        assert_eq!([1.5, 0.0], scaler.transform(&[4.0, 7.0]));
+    }
+
+    #[test]
+    fn robust_scaler() {
+        let inputs = [[1.0], [2.0], [3.0], [4.0], [1000.0]];
+        let scaler = RobustScaler::fit(1, inputs.iter());
+        // median 3, quartiles 2 and 4
+        assert_eq!([0.5], scaler.transform(&[4.0]));
+    }
+
+    #[test]
+    fn quantiles() {
+        let sorted = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(2.5, quantile(&sorted, 0.5));
+        assert_eq!(1.75, quantile(&sorted, 0.25));
+        assert_eq!(4.0, quantile(&sorted, 1.0));
+    }
+
+    #[test]
+    fn polynomial_features() {
+        let expansion = PolynomialFeatures::new(2, 3);
+        assert_eq!(9, expansion.output_dimension());
+        assert_eq!(
+            vec![2.0, 3.0, 4.0, 6.0, 9.0, 8.0, 12.0, 18.0, 27.0],
+            expansion.transform(&[2.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn chained_transformers() {
+        let scaler = MinMaxScaler::fit(2, [[0.0, 0.0], [4.0, 2.0]].iter());
+        let chain = (scaler, PolynomialFeatures::new(2, 2));
+        assert_eq!(
+            vec![0.5, 0.5, 0.25, 0.25, 0.25],
+            chain.transform(&[2.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a(b"a"));
+        assert_eq!(0x8594_4171_f739_67e8, fnv1a(b"foobar"));
+    }
+
+    #[test]
+    fn feature_hasher() {
+        let hasher = FeatureHasher::new(1000);
+        let a = hasher.transform(&vec!["a"]);
+        assert_eq!(1.0, a.iter().map(|x: &f64| x.abs()).sum::<f64>());
+        let twice = hasher.transform(&vec!["a", "a"]);
+        assert_eq!(a.iter().map(|x| 2.0 * x).collect::<Vec<_>>(), twice);
+        assert!(hasher
+            .transform(&Vec::<&str>::new())
+            .iter()
+            .all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn one_hot_encoder_bucket() {
+        let rows = vec![vec!["b", "x"], vec!["a", "y"], vec!["c", "x"]];
+        let encoder = OneHotEncoder::fit(2, Unknown::Bucket, &rows);
+        assert_eq!(7, encoder.output_dimension());
+        assert_eq!(
+            vec![0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            encoder.transform(&vec!["b", "y"])
+        );
+        assert_eq!(
+            vec![0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0],
+            encoder.transform(&vec!["d", "x"])
+        );
+    }
+
+    #[test]
+    fn one_hot_encoder_ignore() {
+        let rows = vec![vec!["b".to_string()], vec!["a".to_string()]];
+        let encoder = OneHotEncoder::fit(1, Unknown::Ignore, rows);
+        assert_eq!(2, encoder.output_dimension());
+        assert_eq!(vec![1.0, 0.0], encoder.transform(&vec!["a".to_string()]));
+        assert_eq!(vec![0.0, 0.0], encoder.transform(&vec!["c".to_string()]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of categories does not match the columns of the encoder")]
+    fn one_hot_encoder_wrong_dimension() {
+        let encoder = OneHotEncoder::fit(2, Unknown::Ignore, &[vec!["a", "b"]]);
+        encoder.transform(&vec!["a"]);
+    }
+
+    #[test]
+    fn label_encoder_round_trip() {
+        let labels = LabelEncoder::fit(&["b", "c", "a", "c"]);
+        for class in 0..labels.num_classes() {
+            assert_eq!(Some(class), labels.encode(labels.decode(class).unwrap()));
+        }
+        assert_eq!(None, labels.decode(3));
+    }
+
+    #[test]
+    fn imputer_median() {
+        let rows = vec![
+            vec![Some(1.0), None],
+            vec![Some(2.0), None],
+            vec![Some(100.0), None],
+        ];
+        let imputer = Imputer::fit(2, Strategy::Median, false, rows);
+        assert_eq!(&[2.0, 0.0], imputer.fill_values());
+        assert_eq!(vec![2.0, 0.0], imputer.transform(&vec![None, None]));
+    }
+
+    #[test]
+    fn imputer_constant() {
+        let imputer = Imputer::fit(2, Strategy::Constant(-1.0), false, &[vec![Some(5.0), None]]);
+        assert_eq!(2, imputer.output_dimension());
+        assert_eq!(vec![3.0, -1.0], imputer.transform(&vec![Some(3.0), None]));
+    }
+
+    #[test]
+    fn imputer_mean_online() {
+        let rows = vec![
+            vec![Some(1.0), Some(2.0)],
+            vec![None, Some(4.0)],
+            vec![Some(5.0), None],
+        ];
+        let mut imputer = Imputer::new(2, Strategy::Mean, true);
+        for row in &rows {
+            imputer.update(row);
+        }
+        assert_eq!(&[3.0, 3.0], imputer.fill_values());
+        assert_eq!(4, imputer.output_dimension());
+        let fitted = Imputer::fit(2, Strategy::Mean, true, &rows);
+        assert_eq!(imputer.fill_values(), fitted.fill_values());
+    }
+
+    #[test]
+    fn imputer_median_online() {
+        let mut imputer = Imputer::new(1, Strategy::Median, false);
+        for x in &[1.0, 100.0, 2.0] {
+            imputer.update(&[Some(*x)]);
+        }
+        // Exact for the first five values
+        assert_eq!(&[2.0], imputer.fill_values());
+
+        // Values 0 to 1000 in a scrambled order
+        for i in 0..1001 {
+            imputer.update(&[Some(((i * 367) % 1001) as f64)]);
+        }
+        assert!((imputer.fill_values()[0] - 500.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn imputer_median_fit_then_update() {
+        let rows: Vec<_> = (0..101).map(|i| vec![Some(i as f64)]).collect();
+        let mut imputer = Imputer::fit(1, Strategy::Median, false, &rows);
+        assert_eq!(&[50.0], imputer.fill_values());
+        for i in 101..201 {
+            imputer.update(&[Some(i as f64)]);
+        }
+        assert!((imputer.fill_values()[0] - 100.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn imputer_median_fit_five_then_update() {
+        let rows: Vec<_> = (0..5).map(|i| vec![Some(i as f64)]).collect();
+        let mut imputer = Imputer::fit(1, Strategy::Median, false, &rows);
+        assert_eq!(&[2.0], imputer.fill_values());
+        for i in 5..501 {
+            imputer.update(&[Some(i as f64)]);
+        }
+        assert!((imputer.fill_values()[0] - 250.0).abs() < 10.0);
+        // Only the five markers are kept
+        assert_eq!(5, imputer.medians[0].heights.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of values does not match the columns of the imputer")]
+    fn imputer_wrong_dimension() {
+        let imputer = Imputer::new(2, Strategy::Mean, false);
+        imputer.transform(&vec![Some(1.0)]);
+    }
+}
@@ -189,7 +189,7 @@ fn logistic_sgd_2d_least_squares() {
 
     println!("{:?}", model);
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).round() == truth)
         .fold(
@@ -230,7 +230,7 @@ fn logistic_sgd_2d_max_likelihood() {
 
     println!("{:?}", model);
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).round() == truth)
         .map(|correct| if correct { 0 } else { 1 })
@@ -269,7 +269,7 @@ fn logistic_sgd_2d_max_likelihood_bool() {
 
     println!("{:?}", model);
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).crisp() == truth)
         .map(|correct| if correct { 0 } else { 1 })
@@ -311,7 +311,7 @@ fn logistic_adagard_2d_max_likelihood_bool() {
 
     println!("{:?}", model);
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).crisp() == truth)
         .map(|correct| if correct { 0 } else { 1 })
@@ -321,7 +321,7 @@ fn logistic_adagard_2d_max_likelihood_bool() {
 }
 
 #[test]
-fn generalized_linear_model_as_logistic_regression() {
+fn logistic_sgd_2d_focal() {
     use vikos::{learn_history, Crisp, Model};
 
     let history = [
@@ -337,10 +337,49 @@ fn generalized_linear_model_as_logistic_regression() {
         ([7.6, 3.5], true),
     ];
 
-    let mut model = model::GeneralizedLinearModel::new(
-        |x| 1.0 / (1.0 + x.exp()),
-        |x| -x.exp() / (1.0 + x.exp()).powi(2),
+    let mut model = model::Logistic::default();
+    let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+    let cost = cost::Focal {
+        gamma: 2.0,
+        alpha: 0.5,
+    };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(40).cloned(),
     );
+
+    println!("{:?}", model);
+
+    let classification_errors: i32 = history
+        .iter()
+        .map(|&(input, truth)| model.predict(&input).crisp() == truth)
+        .map(|correct| if correct { 0 } else { 1 })
+        .sum();
+
+    assert_eq!(0, classification_errors);
+}
+
+#[test]
+fn generalized_linear_model_as_logistic_regression() {
+    use vikos::{dual::Number, learn_history, Crisp, Model};
+
+    let history = [
+        ([2.7, 2.5], false),
+        ([1.4, 2.3], false),
+        ([3.3, 4.4], false),
+        ([1.3, 1.8], false),
+        ([3.0, 3.0], false),
+        ([7.6, 2.7], true),
+        ([5.3, 2.0], true),
+        ([6.9, 1.7], true),
+        ([8.6, -0.2], true),
+        ([7.6, 3.5], true),
+    ];
+
+    let mut model = model::GeneralizedLinearModel::new(|x| 1.0 / (1.0 + x.exp()));
     let teacher = teacher::GradientDescent { learning_rate: 0.3 };
     let cost = cost::MaxLikelihood {};
 
@@ -351,7 +390,7 @@ fn generalized_linear_model_as_logistic_regression() {
         history.iter().cycle().take(20).cloned(),
     );
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).crisp() == truth)
         .map(|correct| if correct { 0 } else { 1 })
@@ -402,7 +441,7 @@ fn iris() {
 
     println!("{:?}", model);
 
-    let classification_errors = history
+    let classification_errors: i32 = history
         .iter()
         .map(|&(input, truth)| model.predict(&input).crisp() == truth)
         .map(|correct| if correct { 0 } else { 1 })
@@ -410,3 +449,802 @@ fn iris() {
 
     assert_eq!(3, classification_errors);
 }
+
+#[test]
+fn gradient_clipping_by_value() {
+    use vikos::learn_history;
+
+    let history = [(1000.0, 1000.0)];
+    let mut model = model::Linear { m: 0.0, c: 0.0 };
+    let teacher = teacher::GradientClipping {
+        teacher: teacher::GradientDescent { learning_rate: 1.0 },
+        clip: teacher::Clip::Value(0.5),
+    };
+    let cost = cost::LeastSquares {};
+
+    learn_history(&teacher, &cost, &mut model, history.iter().cloned());
+
+    assert_eq!(0.5, model.m);
+    assert_eq!(0.5, model.c);
+}
+
+#[test]
+#[should_panic(expected = "Clipping threshold must be finite and not negative")]
+fn gradient_clipping_rejects_negative_threshold() {
+    use vikos::Teacher;
+
+    let model = model::Linear { m: 0.0, c: 0.0 };
+    let teacher = teacher::GradientClipping {
+        teacher: teacher::GradientDescent { learning_rate: 1.0 },
+        clip: teacher::Clip::Value(-0.5),
+    };
+    teacher.new_training(&model);
+}
+
+#[test]
+#[should_panic(expected = "Decay of exponential average must be within [0, 1]")]
+fn averaged_rejects_invalid_decay() {
+    use vikos::Teacher;
+
+    let model = model::Linear { m: 0.0, c: 0.0 };
+    let teacher = teacher::Averaged {
+        teacher: teacher::GradientDescent { learning_rate: 1.0 },
+        average: teacher::Average::Exponential(1.5),
+    };
+    teacher.new_training(&model);
+}
+
+#[test]
+fn gradient_clipping_by_norm() {
+    use vikos::learn_history;
+
+    let history = [(1000.0, 1000.0)];
+    let mut model = model::Linear { m: 0.0, c: 0.0 };
+    let teacher = teacher::GradientClipping {
+        teacher: teacher::GradientDescent { learning_rate: 1.0 },
+        clip: teacher::Clip::Norm(1.0),
+    };
+    let cost = cost::LeastSquares {};
+
+    learn_history(&teacher, &cost, &mut model, history.iter().cloned());
+
+    assert!((model.m.powi(2) + model.c.powi(2) - 1.0).abs() < 1e-9);
+    assert!(model.m > 0.999);
+}
+
+#[test]
+fn linear_sgd_2d_clipped() {
+    use vikos::learn_history;
+
+    let history = [([0.0, 7.0], 17.0), ([1.0, 2.0], 8.0), ([2.0, -2.0], 1.0)];
+    let mut model = model::Linear {
+        m: [0.0, 0.0],
+        c: 0.0,
+    };
+    let cost = cost::LeastSquares {};
+    let teacher = teacher::GradientClipping {
+        teacher: teacher::Momentum {
+            l0: 0.009,
+            t: 1000.0,
+            inertia: 0.995,
+        },
+        clip: teacher::Clip::Norm(50.0),
+    };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(3000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    assert!(model.m[0] < 1.1);
+    assert!(model.m[0] > 0.9);
+    assert!(model.m[1] < 2.1);
+    assert!(model.m[1] > 1.9);
+    assert!(model.c < 3.1);
+    assert!(model.c > 2.9);
+}
+
+#[test]
+fn estimate_weighted_mean() {
+    use vikos::learn_weighted_history;
+
+    let history = [((), 1.0, 1.0), ((), 4.0, 2.0)]; //weighted mean is three
+
+    let cost = cost::LeastSquares {};
+    let mut model = 0.0;
+
+    let teacher = teacher::GradientDescentAl { l0: 0.1, t: 20.0 };
+
+    learn_weighted_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(200).cloned(),
+    );
+
+    println!("model: {:?}", model);
+
+    assert!(model < 3.1);
+    assert!(model > 2.9);
+}
+
+#[test]
+fn weighted_mean_cost() {
+    use vikos::{mean_cost, weighted_mean_cost};
+
+    let model = 2.0;
+    let cost = cost::LeastSquares {};
+
+    assert_eq!(2.5, mean_cost(&model, &cost, vec![((), 1.0), ((), 4.0)]));
+    assert_eq!(
+        3.0,
+        weighted_mean_cost(&model, &cost, vec![((), 1.0, 1.0), ((), 4.0, 2.0)])
+    );
+}
+
+#[test]
+fn logistic_class_weighted_imbalanced() {
+    use vikos::{learn_history, Crisp, Model};
+
+    // At x = 1 only one in five events is positive
+    let history: Vec<_> = (0..19)
+        .map(|_| ([0.0], false))
+        .chain((0..4).map(|_| ([1.0], false)))
+        .chain(Some(([1.0], true)))
+        .collect();
+
+    let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+    let cost = cost::MaxLikelihood {};
+    let mut model = model::Logistic::default();
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(2400).cloned(),
+    );
+    assert!(!model.predict(&[1.0]).crisp());
+
+    let cost = cost::ClassWeighted::balanced(cost, 2, history.iter().cloned());
+    let mut model = model::Logistic::default();
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(2400).cloned(),
+    );
+    assert!(model.predict(&[1.0]).crisp());
+    assert!(!model.predict(&[0.0]).crisp());
+}
+
+#[test]
+fn iris_focal() {
+    use vikos::{learn_history, Crisp, Model};
+
+    let mut model = model::OneVsRest::<[model::Logistic<[f64; 4]>; 3]>::default();
+    let teacher = vikos::teacher::Nesterov {
+        l0: 0.0001,
+        t: 1000.0,
+        inertia: 0.99,
+    };
+    let cost = cost::Focal {
+        gamma: 1.0,
+        alpha: 0.5,
+    };
+
+    let history: Vec<_> = csv::Reader::from_path("examples/data/iris.csv")
+        .expect("File is ok")
+        .deserialize()
+        .map(|row| {
+            let (t, f): (String, _) = row.unwrap();
+            (t, f)
+        })
+        .map(|(truth, features)| {
+            (
+                features,
+                match truth.as_ref() {
+                    "setosa" => 0,
+                    "versicolor" => 1,
+                    "virginica" => 2,
+                    _ => panic!("unknow class"),
+                },
+            )
+        })
+        .collect();
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(3000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    let classification_errors: usize = history
+        .iter()
+        .map(|&(input, truth)| model.predict(&input).crisp() == truth)
+        .map(|correct| if correct { 0 } else { 1 })
+        .sum();
+
+    println!("classification errors: {}", classification_errors);
+    assert!(classification_errors < 10);
+}
+
+#[test]
+fn polynomial_sgd_2d() {
+    use vikos::{learn_history, Model};
+
+    // y = x0^2 - x0 * x1 + 1
+    let history: Vec<_> = [-1.0, -0.5, 0.0, 0.5, 1.0]
+        .iter()
+        .flat_map(|&x0| {
+            [-1.0, 0.0, 1.0]
+                .iter()
+                .map(move |&x1| ([x0, x1], x0 * x0 - x0 * x1 + 1.0))
+        })
+        .collect();
+
+    let mut model = model::Polynomial::new(2, 2);
+    let cost = cost::LeastSquares {};
+    let teacher = teacher::Momentum {
+        l0: 0.01,
+        t: 1000.0,
+        inertia: 0.9,
+    };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(3000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    for &(features, truth) in &history {
+        assert!((model.predict(&features) - truth).abs() < 0.05);
+    }
+}
+
+#[test]
+fn factorization_machine_interactions() {
+    use vikos::{learn_history, Model};
+
+    // One hot encoded store (first two features) and product (last two features). The target
+    // depends only on the interaction between store and product, which a linear model can not fit.
+    let history = [
+        ([1.0, 0.0, 1.0, 0.0], 1.0),
+        ([1.0, 0.0, 0.0, 1.0], -1.0),
+        ([0.0, 1.0, 1.0, 0.0], -1.0),
+        ([0.0, 1.0, 0.0, 1.0], 1.0),
+    ];
+
+    let mut model = model::FactorizationMachine::new(4, 2, 42);
+    let cost = cost::LeastSquares {};
+    let teacher = teacher::GradientDescent {
+        learning_rate: 0.05,
+    };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(4000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    for &(features, truth) in &history {
+        assert!((model.predict(&features) - truth).abs() < 0.1);
+    }
+}
+
+#[test]
+fn mlp_xor() {
+    use vikos::{learn_history, model::Activation, Crisp, Model};
+
+    let history = [
+        ([0.0, 0.0], false),
+        ([0.0, 1.0], true),
+        ([1.0, 0.0], true),
+        ([1.0, 1.0], false),
+    ];
+
+    let mut model = model::Mlp::new(2, &[(4, Activation::Tanh)], Activation::Sigmoid, 42);
+    let teacher = teacher::Momentum {
+        l0: 0.1,
+        t: 1000.0,
+        inertia: 0.9,
+    };
+    let cost = cost::MaxLikelihood {};
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(4000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    let classification_errors: i32 = history
+        .iter()
+        .map(|&(input, truth)| model.predict(&input).crisp() == truth)
+        .map(|correct| if correct { 0 } else { 1 })
+        .sum();
+
+    assert_eq!(0, classification_errors);
+}
+
+#[test]
+fn kernel_regression_random_fourier_features() {
+    use vikos::{
+        learn_history,
+        transform::{RandomFourierFeatures, Transformer},
+        Model,
+    };
+
+    // y = sin(x), which can not be fitted by a linear model of x
+    let history: Vec<_> = (0..30)
+        .map(|i| {
+            let x = i as f64 * 0.2;
+            ([x], x.sin())
+        })
+        .collect();
+
+    let rff = RandomFourierFeatures::new(1, 200, 1.0, 42);
+    let mut model = model::Linear::with_feature_dimension(rff.output_dimension());
+    let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+    let cost = cost::LeastSquares {};
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history
+            .iter()
+            .cycle()
+            .take(6000)
+            .map(|(x, y)| (rff.transform(x), *y)),
+    );
+
+    for (x, y) in &history {
+        let prediction = model.predict(&rff.transform(x));
+        assert!((prediction - y).abs() < 0.1);
+    }
+}
+
+#[test]
+fn iris_standard_scaler() {
+    use vikos::{learn_history, transform::StandardScaler, Crisp, Model};
+
+    let history: Vec<_> = csv::Reader::from_path("examples/data/iris.csv")
+        .expect("File is ok")
+        .deserialize()
+        .map(|row| {
+            let (t, f): (String, [f64; 4]) = row.unwrap();
+            (t, f)
+        })
+        .map(|(truth, features)| {
+            (
+                features,
+                match truth.as_ref() {
+                    "setosa" => 0,
+                    "versicolor" => 1,
+                    "virginica" => 2,
+                    _ => panic!("unknow class"),
+                },
+            )
+        })
+        .collect();
+
+    // With scaled features a much larger learning rate can be used
+    let mut model = model::Pipeline {
+        transformer: StandardScaler::fit(4, history.iter().map(|(x, _)| x)),
+        model: model::OneVsRest::<[model::Logistic<[f64; 4]>; 3]>::default(),
+    };
+    let teacher = teacher::GradientDescent { learning_rate: 0.1 };
+    let cost = cost::MaxLikelihood {};
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(15000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    let classification_errors: usize = history
+        .iter()
+        .map(|&(input, truth)| model.predict(&input).crisp() == truth)
+        .map(|correct| if correct { 0 } else { 1 })
+        .sum();
+
+    println!("classification errors: {}", classification_errors);
+    assert!(classification_errors <= 6);
+}
+
+#[test]
+fn pipeline_scaled_polynomial() {
+    use vikos::{
+        learn_history,
+        transform::{PolynomialFeatures, StandardScaler},
+        Model,
+    };
+
+    // y = x0^2 - x0 * x1 + 1, with the second feature on a much larger scale
+    let history: Vec<_> = [-1.0, -0.5, 0.0, 0.5, 1.0]
+        .iter()
+        .flat_map(|&x0| {
+            [-100.0, 0.0, 100.0]
+                .iter()
+                .map(move |&x1| ([x0, x1], x0 * x0 - x0 * x1 / 100.0 + 1.0))
+        })
+        .collect();
+
+    let expansion = PolynomialFeatures::new(2, 2);
+    let mut model = model::Pipeline {
+        transformer: (
+            StandardScaler::fit(2, history.iter().map(|(x, _)| x)),
+            expansion.clone(),
+        ),
+        model: model::Linear::with_feature_dimension(expansion.output_dimension()),
+    };
+    let cost = cost::LeastSquares {};
+    let teacher = teacher::Momentum {
+        l0: 0.01,
+        t: 1000.0,
+        inertia: 0.9,
+    };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(3000).cloned(),
+    );
+
+    println!("{:?}", model);
+
+    assert_eq!(6, model.num_coefficients());
+    for &(features, truth) in &history {
+        assert!((model.predict(&features) - truth).abs() < 0.05);
+    }
+}
+
+#[test]
+fn pipeline_feature_hasher() {
+    use vikos::{learn_history, transform::FeatureHasher, Crisp, Model};
+
+    let history = [
+        (vec!["store=berlin", "product=milk"], true),
+        (vec!["store=berlin", "product=bread"], false),
+        (vec!["store=hamburg", "product=milk"], true),
+        (vec!["store=hamburg", "product=bread"], false),
+    ];
+
+    let mut model = model::Pipeline {
+        transformer: FeatureHasher::new(64),
+        model: model::Logistic::with_feature_dimension(64),
+    };
+    let cost = cost::MaxLikelihood {};
+    let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(40).cloned(),
+    );
+
+    for (features, truth) in &history {
+        assert_eq!(*truth, model.predict(features).crisp());
+    }
+}
+
+#[test]
+fn pipeline_imputer() {
+    use vikos::{
+        learn_history,
+        transform::{Imputer, Strategy},
+        Model,
+    };
+
+    // y = 2 * x0 + 1, but an additional 3 if x1 is missing
+    let history = [
+        (vec![Some(0.0), Some(1.0)], 1.0),
+        (vec![Some(1.0), None], 6.0),
+        (vec![Some(2.0), Some(0.5)], 5.0),
+        (vec![Some(3.0), None], 10.0),
+    ];
+
+    let imputer = Imputer::fit(2, Strategy::Mean, true, history.iter().map(|(x, _)| x));
+    let mut model = model::Pipeline {
+        model: model::Linear::with_feature_dimension(imputer.output_dimension()),
+        transformer: imputer,
+    };
+    let teacher = teacher::Momentum {
+        l0: 0.01,
+        t: 1000.0,
+        inertia: 0.9,
+    };
+    let cost = cost::LeastSquares {};
+
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(2000).cloned(),
+    );
+
+    for (features, truth) in &history {
+        assert!((model.predict(features) - truth).abs() < 0.1);
+    }
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_history_petal_width() {
+    use vikos::{dataset::CsvHistory, mean_cost, try_learn_history};
+
+    let open = || {
+        CsvHistory::from_path(
+            "examples/data/iris.csv",
+            &["petal length (cm)"],
+            "petal width (cm)",
+        )
+        .expect("File is ok")
+    };
+    let mut model = model::Linear::<[f64; 1]>::default();
+    let teacher = teacher::GradientDescent {
+        learning_rate: 0.01,
+    };
+    let cost = cost::LeastSquares {};
+
+    for _ in 0..50 {
+        try_learn_history(&teacher, &cost, &mut model, open()).unwrap();
+    }
+
+    let history: Vec<([f64; 1], f64)> = open().map(Result::unwrap).collect();
+    let error = mean_cost(&model, &cost, history);
+    println!("{:?}, mean cost: {}", model, error);
+    assert!(error < 0.1);
+}
+
+#[test]
+fn libsvm_sparse_logistic_regression() {
+    use vikos::{dataset::LibSvmReader, learn_history, linear_algebra::SparseVector, Crisp, Model};
+
+    // Label is true if feature 3 is present, feature 1000 is noise
+    let data = "+1 3:1 1000:1\n-1 7:1\n+1 3:1 42:1\n-1 42:1 1000:1\n-1 1:1\n+1 3:1 7:1\n";
+    let history: Vec<(SparseVector, bool)> = LibSvmReader::from_reader(data.as_bytes(), 1000)
+        .events()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let mut model = model::Logistic::<SparseVector>::with_feature_dimension(1000);
+    let teacher = teacher::GradientDescent { learning_rate: 0.5 };
+    let cost = cost::MaxLikelihood {};
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(300).cloned(),
+    );
+
+    for (features, truth) in &history {
+        assert_eq!(*truth, model.predict(features).crisp());
+    }
+}
+
+#[test]
+fn shuffle_sorted_stream() {
+    use vikos::{dataset::shuffle, learn_history};
+
+    // Sorted stream, like events ordered by time. Mean is 50.5
+    let history = || (1..101).map(|truth| ((), f64::from(truth)));
+    let teacher = teacher::GradientDescent {
+        learning_rate: 0.05,
+    };
+    let cost = cost::LeastSquares {};
+
+    let mut sorted = 0.0;
+    learn_history(&teacher, &cost, &mut sorted, history());
+    let mut shuffled = 0.0;
+    learn_history(&teacher, &cost, &mut shuffled, shuffle(history(), 100, 1));
+
+    println!("sorted: {}, shuffled: {}", sorted, shuffled);
+    assert!(sorted > 75.0);
+    assert!((shuffled - 50.5).abs() < 20.0);
+}
+
+#[test]
+fn onnx_export_matches_prediction() {
+    use vikos::{learn_history, linear_algebra::Vector, onnx::Export, Model};
+
+    let history = [
+        ([0.0, 1.0], 0),
+        ([1.0, 0.0], 1),
+        ([0.0, -1.0], 2),
+        ([-1.0, 0.0], 2),
+    ];
+    let mut model = model::OneVsRest::<[model::Logistic<[f64; 2]>; 3]>::default();
+    let teacher = teacher::GradientDescent { learning_rate: 0.3 };
+    let cost = cost::MaxLikelihood {};
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(200).cloned(),
+    );
+
+    let graph = model.to_onnx().unwrap();
+    for (features, _) in &history {
+        let expected = model.predict(features);
+        let actual = graph.evaluate(features);
+        for (class, value) in actual.into_iter().enumerate() {
+            assert!((expected.at(class) - value).abs() < 1e-12);
+        }
+    }
+    assert!(!graph.to_bytes().is_empty());
+}
+
+#[test]
+fn averaged_coefficients_reduce_jitter() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use vikos::{mean_cost, Teacher};
+
+    // y = 2x + 1 with noise
+    let mut rng = StdRng::seed_from_u64(11);
+    let history: Vec<(f64, f64)> = (0..500)
+        .map(|_| {
+            let x = rng.gen_range(-1.0..1.0);
+            (x, 2.0 * x + 1.0 + rng.gen_range(-1.0..1.0))
+        })
+        .collect();
+
+    let cost = cost::LeastSquares {};
+    let mut model = model::Linear { m: 0.0, c: 0.0 };
+    let teacher = teacher::Averaged {
+        teacher: teacher::GradientDescent { learning_rate: 0.2 },
+        average: teacher::Average::Polyak,
+    };
+    let mut training = teacher.new_training(&model);
+    for &(features, truth) in history.iter().cycle().take(2000) {
+        teacher.teach_event(&mut training, &mut model, &cost, &features, truth);
+    }
+    let averaged = training.averaged_model(&model);
+    assert_eq!(2000, training.events);
+
+    let exact = model::Linear { m: 2.0, c: 1.0 };
+    let distance = |m: &model::Linear<f64>| (m.m - exact.m).abs() + (m.c - exact.c).abs();
+    println!("latest: {:?}, averaged: {:?}", model, averaged);
+    assert!(distance(&averaged) < distance(&model));
+    assert!(mean_cost(&averaged, &cost, history.clone()) < mean_cost(&model, &cost, history));
+}
+
+#[test]
+fn exponential_moving_average() {
+    use vikos::Teacher;
+
+    // The model jumps between 0 and 10, the average of the last events stays in between
+    let cost = cost::LeastSquares {};
+    let mut model = 0.0;
+    let teacher = teacher::Averaged {
+        teacher: teacher::GradientDescent { learning_rate: 0.5 },
+        average: teacher::Average::Exponential(0.9),
+    };
+    let mut training = teacher.new_training(&model);
+    for &truth in [0.0, 10.0].iter().cycle().take(1000) {
+        teacher.teach_event(&mut training, &mut model, &cost, &(), truth);
+    }
+    let averaged = training.averaged_model(&model);
+    println!("latest: {}, averaged: {}", model, averaged);
+    assert_eq!(10.0, model);
+    assert!((averaged - 5.0).abs() < 0.5);
+}
+
+#[test]
+fn least_squares_baseline_for_sgd() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use vikos::{learn_history, mean_cost, solve};
+
+    let mut rng = StdRng::seed_from_u64(5);
+    let history: Vec<([f64; 2], f64)> = (0..200)
+        .map(|_| {
+            let x = [rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)];
+            (x, 3.0 * x[0] - x[1] + 0.5 + rng.gen_range(-0.3..0.3))
+        })
+        .collect();
+
+    let exact = solve::least_squares(history.clone()).unwrap();
+
+    let cost = cost::LeastSquares {};
+    let mut model = model::Linear::default();
+    let teacher = teacher::GradientDescent {
+        learning_rate: 0.01,
+    };
+    learn_history(
+        &teacher,
+        &cost,
+        &mut model,
+        history.iter().cycle().take(4000).cloned(),
+    );
+
+    println!("exact: {:?}, sgd: {:?}", exact, model);
+    let optimum = mean_cost(&exact, &cost, history.clone());
+    let sgd = mean_cost(&model, &cost, history);
+    assert!(optimum <= sgd);
+    assert!(sgd - optimum < 0.01);
+}
+
+#[test]
+fn nesterov_derives_model_at_look_ahead_position() {
+    use vikos::{learn_history, Model};
+
+    // The derivative by each coefficient only depends on that coefficient, so it is the same
+    // whether it is evaluated coefficient by coefficient or for all of them at once
+    #[derive(Clone)]
+    struct Squares {
+        a: [f64; 2],
+        batched: bool,
+    }
+
+    impl Model for Squares {
+        type Features = [f64; 2];
+        type Target = f64;
+
+        fn num_coefficients(&self) -> usize {
+            2
+        }
+
+        fn coefficient(&mut self, index: usize) -> &mut f64 {
+            &mut self.a[index]
+        }
+
+        fn predict(&self, input: &[f64; 2]) -> f64 {
+            self.a[0] * self.a[0] * input[0] + self.a[1] * self.a[1] * input[1]
+        }
+
+        fn gradient(&self, coefficient: usize, input: &[f64; 2]) -> f64 {
+            2.0 * self.a[coefficient] * input[coefficient]
+        }
+
+        fn gradients(&self, input: &[f64; 2]) -> Option<Vec<f64>> {
+            if self.batched {
+                Some((0..2).map(|ci| self.gradient(ci, input)).collect())
+            } else {
+                None
+            }
+        }
+    }
+
+    let history = [([1.0, 0.5], 2.0), ([0.5, 1.0], 1.0), ([1.0, 1.0], 2.5)];
+    let teacher = teacher::Nesterov {
+        l0: 0.02,
+        t: 100.0,
+        inertia: 0.5,
+    };
+    let cost = cost::LeastSquares {};
+    let mut models = [false, true].iter().map(|&batched| {
+        let mut model = Squares {
+            a: [0.5, 0.5],
+            batched,
+        };
+        learn_history(
+            &teacher,
+            &cost,
+            &mut model,
+            history.iter().cycle().take(30).cloned(),
+        );
+        model.a
+    });
+    let (a, b) = (models.next().unwrap(), models.next().unwrap());
+    assert!(a.iter().all(|x| x.is_finite()));
+    assert_eq!(a, b);
+}